@@ -1,7 +1,9 @@
+use crate::airplane::AirplaneOp;
 use crate::args::Command;
 use crate::brightness::BrightnessOp;
+use crate::config::Config;
 use crate::manager::Manager;
-use crate::system::SystemOp;
+use crate::power::Power;
 use crate::volume::VolumeOp;
 use std::io::{BufRead, BufReader};
 use std::os::unix::net::UnixStream;
@@ -12,15 +14,22 @@ pub struct Acpi;
 
 impl Acpi {
     pub fn listen() -> anyhow::Result<()> {
+        // the daemon only waits on this thread, so nothing here may end it
+        if let Err(e) = Power::update(0, true) {
+            eprintln!("Failed to apply power profile ({e})");
+        }
+
         let stream = UnixStream::connect("/run/acpid.socket")?;
         let reader = BufReader::new(stream);
 
         let delay = Duration::from_micros(100);
         let mut last = Instant::now();
-        for line in reader.lines().flatten() {
+        for line in reader.lines().map_while(Result::ok) {
             if last.elapsed() >= delay {
                 let event = line.split(' ').collect::<Vec<&str>>();
-                Self::handle(&event)?;
+                if let Err(e) = Self::handle(&event) {
+                    eprintln!("Failed to handle '{line}' ({e})");
+                }
                 last = Instant::now();
             }
         }
@@ -29,15 +38,15 @@ impl Acpi {
     }
 
     fn handle(event: &[&str]) -> anyhow::Result<()> {
-        match event.get(0) {
+        match event.first() {
             Some(&"button/lid") => match event.get(2) {
                 Some(&"close") => Some(Command::System {
-                    operation: SystemOp::Suspend,
+                    operation: Config::read()?.acpi.lid.into(),
                 }),
                 _ => None,
             },
             Some(&"button/sleep") => Some(Command::System {
-                operation: SystemOp::Suspend,
+                operation: Config::read()?.acpi.sleep.into(),
             }),
             Some(&"video/brightnessup") => Some(Command::Brightness {
                 operation: BrightnessOp::Up { percent: 5 },
@@ -57,6 +66,10 @@ impl Acpi {
             Some(&"jack/headphone") => Some(Command::Volume {
                 operation: VolumeOp::Update,
             }),
+            Some(&"button/wlan") => Some(Command::Airplane {
+                operation: AirplaneOp::Toggle,
+            }),
+            Some(&"ac_adapter") => return Power::update(500, false),
             _ => None,
         }
         .map_or(Ok(()), Manager::handle)
@@ -0,0 +1,180 @@
+use crate::logger::Logger;
+use crate::notifier::Notifier;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+
+const DEVICE: &str = "/dev/rfkill";
+const CLASS: &str = "/sys/class/rfkill";
+// rfkill types from linux/rfkill.h
+const RADIOS: &[(&str, u8)] = &[("wlan", 1), ("bluetooth", 2), ("wwan", 5)];
+const OP_CHANGE_ALL: u8 = 3;
+
+pub struct Airplane;
+
+impl Airplane {
+    pub fn listen() -> anyhow::Result<()> {
+        let mut events = File::open(DEVICE)?;
+        let mut event = [0; 8];
+        let mut current = None;
+        loop {
+            let state = AirplaneState::new(&Self::get_radios()?);
+            if Some(&state) != current.as_ref() {
+                if let Some(prev) = current {
+                    state.notify(prev)?;
+                }
+                state.log()?;
+                current = Some(state);
+            }
+            events.read_exact(&mut event)?;
+        }
+    }
+
+    pub fn on() -> anyhow::Result<()> {
+        let enabled = Self::get_radios()?
+            .into_iter()
+            .filter(|radio| !radio.soft && !radio.hard)
+            .map(|radio| radio.kind)
+            .fold(Vec::new(), |mut kinds, kind| {
+                if !kinds.contains(&kind) {
+                    kinds.push(kind);
+                }
+                kinds
+            });
+        if !enabled.is_empty() {
+            Logger::new("airplane.radios").write(&enabled)?;
+        }
+        for (_, kind) in RADIOS {
+            Self::block(*kind, true)?;
+        }
+        Self::update()
+    }
+
+    pub fn off() -> anyhow::Result<()> {
+        let enabled = Logger::<Vec<String>>::new("airplane.radios")
+            .read()
+            .unwrap_or(RADIOS.iter().map(|(name, _)| name.to_string()).collect());
+        for (name, kind) in RADIOS {
+            if enabled.iter().any(|radio| radio == name) {
+                Self::block(*kind, false)?;
+            }
+        }
+        Self::update()
+    }
+
+    pub fn toggle() -> anyhow::Result<()> {
+        if AirplaneState::new(&Self::get_radios()?).active {
+            Self::off()
+        } else {
+            Self::on()
+        }
+    }
+
+    pub fn update() -> anyhow::Result<()> {
+        let prev = Logger::<AirplaneState>::new("airplane").read().ok();
+        let state = AirplaneState::new(&Self::get_radios()?);
+        if let Some(prev) = prev {
+            state.notify(prev)?;
+        }
+        state.log()
+    }
+
+    pub fn handle(operation: AirplaneOp) -> anyhow::Result<()> {
+        match operation {
+            AirplaneOp::On => Self::on(),
+            AirplaneOp::Off => Self::off(),
+            AirplaneOp::Toggle => Self::toggle(),
+        }
+    }
+
+    fn get_radios() -> anyhow::Result<Vec<Radio>> {
+        let mut radios = Vec::new();
+        for entry in std::fs::read_dir(CLASS)?.flatten() {
+            let read = |file: &str| std::fs::read_to_string(entry.path().join(file));
+            let kind = read("type")?.trim().to_string();
+            if RADIOS.iter().any(|(name, _)| *name == kind) {
+                radios.push(Radio {
+                    kind,
+                    soft: read("soft")?.trim() == "1",
+                    hard: read("hard")?.trim() == "1",
+                });
+            }
+        }
+        Ok(radios)
+    }
+
+    fn block(kind: u8, block: bool) -> anyhow::Result<()> {
+        let event = [0, 0, 0, 0, kind, OP_CHANGE_ALL, block as u8, 0];
+        OpenOptions::new()
+            .write(true)
+            .open(DEVICE)?
+            .write_all(&event)?;
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum AirplaneOp {
+    On,
+    Off,
+    Toggle,
+}
+
+struct Radio {
+    kind: String,
+    soft: bool,
+    hard: bool,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct AirplaneState {
+    active: bool,
+    hard: bool,
+    wifi: bool,
+    bluetooth: bool,
+    wwan: bool,
+    icon: String,
+}
+
+impl AirplaneState {
+    fn new(radios: &[Radio]) -> Self {
+        let enabled = |kind: &str| {
+            radios
+                .iter()
+                .any(|radio| radio.kind == kind && !radio.soft && !radio.hard)
+        };
+        let (wifi, bluetooth, wwan) = (enabled("wlan"), enabled("bluetooth"), enabled("wwan"));
+        let active = !radios.is_empty() && !wifi && !bluetooth && !wwan;
+        let hard = radios.iter().any(|radio| radio.hard);
+        let icon = if active { "󰀝 " } else { "󰀞 " }.to_string();
+        Self {
+            active,
+            hard,
+            wifi,
+            bluetooth,
+            wwan,
+            icon,
+        }
+    }
+
+    pub fn notify(&self, prev: Self) -> anyhow::Result<()> {
+        let notifier = Notifier::new("airplane");
+        if self.hard != prev.hard {
+            let body = if self.hard {
+                "Hardware switch turned radios off"
+            } else {
+                "Hardware switch turned radios on"
+            };
+            notifier.send("Airplane mode", body, None, None)?;
+        } else if self.active != prev.active {
+            let body = if self.active { "Enabled" } else { "Disabled" };
+            notifier.send("Airplane mode", body, None, None)?;
+        }
+        Ok(())
+    }
+
+    pub fn log(&self) -> anyhow::Result<()> {
+        Logger::new("airplane").write(self)
+    }
+}
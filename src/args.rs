@@ -1,8 +1,13 @@
+use crate::airplane::AirplaneOp;
+use crate::battery::BatteryOp;
+use crate::bluetooth::BluetoothOp;
 use crate::brightness::BrightnessOp;
-use crate::hypr::Layout;
+use crate::compositor::LayoutOp;
+use crate::power::PowerOp;
 use crate::system::SystemOp;
 use crate::volume::VolumeOp;
-use clap::{command, Parser, Subcommand};
+use crate::wifi::WifiOp;
+use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(author, version, about)]
@@ -30,9 +35,36 @@ pub enum Command {
         #[command(subcommand)]
         operation: VolumeOp,
     },
-    /// Change layout
-    Layout {
+    /// Commands to manage Wi-Fi
+    Wifi {
+        #[command(subcommand)]
+        operation: WifiOp,
+    },
+    /// Commands to manage Bluetooth
+    Bluetooth {
+        #[command(subcommand)]
+        operation: BluetoothOp,
+    },
+    /// Toggle airplane mode
+    Airplane {
         #[arg(value_enum)]
-        layout: Layout,
+        operation: AirplaneOp,
+    },
+    /// Commands to manage battery
+    Battery {
+        #[command(subcommand)]
+        operation: BatteryOp,
+    },
+    /// Commands to manage power source
+    Power {
+        #[command(subcommand)]
+        operation: PowerOp,
+    },
+    /// Commands to manage keyboard layout
+    Layout {
+        #[command(subcommand)]
+        operation: LayoutOp,
     },
+    /// Toggle a scratchpad, starting its app if needed
+    Scratch { name: String },
 }
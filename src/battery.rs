@@ -1,33 +1,80 @@
+use crate::history::History;
 use crate::logger::Logger;
 use crate::notifier::Notifier;
+use crate::utils::{parse_duration, timestamp};
 use anyhow::anyhow;
-use battery::{units::ratio::percent, Battery as Batt, Manager, State};
+use battery::units::{power::watt, ratio::percent};
+use battery::{Battery as Batt, Manager, State};
+use clap::Subcommand;
 use notify_rust::Urgency;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+// seconds between two history samples
+const SAMPLE: u64 = 60;
+// longest gap between two samples still considered awake
+const AWAKE: u64 = 5 * 60;
+const WEEK: u64 = 60 * 60 * 24 * 7;
+pub const LOW: u32 = 15;
+
 pub struct Battery;
 
 impl Battery {
     pub fn listen() -> anyhow::Result<()> {
         let (manager, mut battery) = Self::get_battery()?;
+        let history = History::new("battery");
         let mut current = None;
+        let mut last = 0;
         loop {
-            let state = Self::get_state(&manager, &mut battery)?;
-            if Some(&state) != current.as_ref() {
+            manager.refresh(&mut battery)?;
+            let state = Self::get_state(&battery);
+            let changed = Some(&state) != current.as_ref();
+            if changed {
                 state.notify()?;
                 state.log()?;
                 current = Some(state)
             }
+            if changed || timestamp().saturating_sub(last) >= SAMPLE {
+                history.push(&Self::get_sample(&battery))?;
+                last = timestamp();
+            }
             std::thread::sleep(Duration::from_secs(2));
         }
     }
 
-    fn get_state(manager: &Manager, battery: &mut Batt) -> anyhow::Result<BatteryState> {
-        manager.refresh(battery)?;
+    pub fn history(since: Duration) -> anyhow::Result<()> {
+        let all = History::<BatterySample>::new("battery").read()?;
+        let start = timestamp().saturating_sub(since.as_secs());
+        let samples = all
+            .iter()
+            .filter(|sample| sample.0 >= start)
+            .cloned()
+            .collect::<Vec<BatterySample>>();
+        let history = BatteryHistory::new(&samples, &all);
+        println!("{}", serde_json::to_string(&history)?);
+        Ok(())
+    }
+
+    pub fn handle(operation: BatteryOp) -> anyhow::Result<()> {
+        match operation {
+            BatteryOp::History { since } => Self::history(since),
+        }
+    }
+
+    fn get_state(battery: &Batt) -> BatteryState {
         let value = battery.state_of_charge().get::<percent>();
         let state = battery.state();
-        Ok(BatteryState::new(value.round() as u32, state))
+        BatteryState::new(value.round() as u32, state)
+    }
+
+    fn get_sample(battery: &Batt) -> BatterySample {
+        BatterySample(
+            timestamp(),
+            battery.state_of_charge().get::<percent>().round() as u32,
+            battery.energy_rate().get::<watt>(),
+            battery.state() == State::Charging,
+            battery.state_of_health().get::<percent>(),
+        )
     }
 
     fn get_battery() -> anyhow::Result<(Manager, Batt)> {
@@ -41,8 +88,17 @@ impl Battery {
     }
 }
 
+#[derive(Clone, Subcommand)]
+pub enum BatteryOp {
+    /// Print charge history and statistics
+    History {
+        #[arg(long, default_value = "24h", value_parser = parse_duration)]
+        since: Duration,
+    },
+}
+
 #[derive(Serialize, Deserialize, PartialEq)]
-struct BatteryState {
+pub struct BatteryState {
     value: u32,
     status: String,
     icon: String,
@@ -50,22 +106,7 @@ struct BatteryState {
 
 impl BatteryState {
     pub fn new(value: u32, state: State) -> Self {
-        let icon = if state == State::Charging {
-            " "
-        } else if state == State::Full || state == State::Unknown {
-            " "
-        } else if value >= 85 {
-            " "
-        } else if value >= 60 {
-            " "
-        } else if value >= 40 {
-            " "
-        } else if value >= 15 {
-            " "
-        } else {
-            " "
-        }
-        .to_string();
+        let icon = Self::icon(value, state);
         let status = match state {
             State::Unknown => "Not charging",
             State::Charging => "Charging",
@@ -82,6 +123,25 @@ impl BatteryState {
         }
     }
 
+    pub fn icon(value: u32, state: State) -> String {
+        if state == State::Charging {
+            " "
+        } else if state == State::Full || state == State::Unknown {
+            " "
+        } else if value >= 85 {
+            " "
+        } else if value >= 60 {
+            " "
+        } else if value >= 40 {
+            " "
+        } else if value >= LOW {
+            " "
+        } else {
+            " "
+        }
+        .to_string()
+    }
+
     pub fn notify(&self) -> anyhow::Result<()> {
         if self.status != "Charging" && self.value <= 10 {
             Notifier::new("battery").send(
@@ -98,3 +158,108 @@ impl BatteryState {
         Logger::new("battery").write(self)
     }
 }
+
+// time, value, rate, charging, health
+#[derive(Serialize, Deserialize, Clone)]
+struct BatterySample(u64, u32, f32, bool, f32);
+
+#[derive(Serialize)]
+struct BatteryPoint {
+    time: u64,
+    value: u32,
+    rate: f32,
+    charging: bool,
+}
+
+#[derive(Serialize)]
+struct BatteryWeek {
+    week: u64,
+    health: f32,
+}
+
+#[derive(Serialize)]
+struct BatteryHistory {
+    samples: Vec<BatteryPoint>,
+    discharge_rate: f32,
+    discharge_rate_max: f32,
+    screen_on: u64,
+    degradation: Vec<BatteryWeek>,
+}
+
+impl BatteryHistory {
+    pub fn new(samples: &[BatterySample], all: &[BatterySample]) -> Self {
+        let rates = samples
+            .iter()
+            .filter(|sample| !sample.3 && sample.2 > 0.)
+            .map(|sample| sample.2)
+            .collect::<Vec<f32>>();
+        let discharge_rate = if rates.is_empty() {
+            0.
+        } else {
+            rates.iter().sum::<f32>() / rates.len() as f32
+        };
+        let discharge_rate_max = rates.iter().copied().fold(0., f32::max);
+        Self {
+            samples: samples
+                .iter()
+                .map(|sample| BatteryPoint {
+                    time: sample.0,
+                    value: sample.1,
+                    rate: sample.2,
+                    charging: sample.3,
+                })
+                .collect(),
+            discharge_rate,
+            discharge_rate_max,
+            screen_on: Self::screen_on(samples),
+            degradation: Self::degradation(all),
+        }
+    }
+
+    // average awake time spent discharging between two charges
+    fn screen_on(samples: &[BatterySample]) -> u64 {
+        let mut charges = Vec::new();
+        let mut current = 0;
+        for pair in samples.windows(2) {
+            let (prev, next) = (&pair[0], &pair[1]);
+            // the clock can step backwards, e.g. when synced at boot
+            let elapsed = next.0.saturating_sub(prev.0);
+            if !prev.3 && !next.3 && elapsed <= AWAKE {
+                current += elapsed;
+            } else if next.3 && current > 0 {
+                charges.push(current);
+                current = 0;
+            }
+        }
+        if current > 0 {
+            charges.push(current);
+        }
+        if charges.is_empty() {
+            0
+        } else {
+            charges.iter().sum::<u64>() / charges.len() as u64
+        }
+    }
+
+    // average health per week over the whole history
+    fn degradation(samples: &[BatterySample]) -> Vec<BatteryWeek> {
+        let mut weeks: Vec<(u64, f32, u32)> = Vec::new();
+        for sample in samples {
+            let week = sample.0 / WEEK * WEEK;
+            match weeks.last_mut() {
+                Some(last) if last.0 == week => {
+                    last.1 += sample.4;
+                    last.2 += 1;
+                }
+                _ => weeks.push((week, sample.4, 1)),
+            }
+        }
+        weeks
+            .into_iter()
+            .map(|(week, health, count)| BatteryWeek {
+                week,
+                health: health / count as f32,
+            })
+            .collect()
+    }
+}
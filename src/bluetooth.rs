@@ -0,0 +1,284 @@
+use crate::logger::Logger;
+use crate::notifier::Notifier;
+use crate::system::System;
+use anyhow::anyhow;
+use clap::{Subcommand, ValueEnum};
+use notify_rust::Urgency;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use zbus::blocking::{Connection, MessageIterator, Proxy, ProxyBuilder};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+use zbus::{CacheProperties, MatchRule, MessageType};
+
+const BLUEZ: &str = "org.bluez";
+const BLUEZ_PATH: &str = "/org/bluez";
+const BLUEZ_ADAPTER: &str = "org.bluez.Adapter1";
+const BLUEZ_DEVICE: &str = "org.bluez.Device1";
+const BLUEZ_BATTERY: &str = "org.bluez.Battery1";
+const OBJECT_MANAGER: &str = "org.freedesktop.DBus.ObjectManager";
+
+type Objects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+pub struct Bluetooth;
+
+impl Bluetooth {
+    pub fn listen() -> anyhow::Result<()> {
+        let bus = System::bus()?;
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .path_namespace(BLUEZ_PATH)?
+            .build();
+        let signals = MessageIterator::for_match_rule(rule, bus, None)?;
+        let mut current = None;
+        Self::update(Self::get_state(bus)?, &mut current)?;
+        for signal in signals {
+            signal?;
+            Self::update(Self::get_state(bus)?, &mut current)?;
+        }
+        Ok(())
+    }
+
+    pub fn power(operation: BluetoothPowerOp) -> anyhow::Result<()> {
+        let bus = System::bus()?;
+        let objects = Self::get_objects(bus)?;
+        let (path, powered) = Self::get_adapter(&objects)?;
+        let powered = match operation {
+            BluetoothPowerOp::On => true,
+            BluetoothPowerOp::Off => false,
+            BluetoothPowerOp::Toggle => !powered,
+        };
+        Self::proxy(bus, path.as_str(), BLUEZ_ADAPTER)?.set_property("Powered", powered)?;
+        let body = if powered { "Turned on" } else { "Turned off" };
+        Notifier::new("bluetooth").send("Bluetooth", body, None, None)
+    }
+
+    pub fn list() -> anyhow::Result<()> {
+        let state = Self::get_state(System::bus()?)?;
+        println!("{}", serde_json::to_string(&state.devices)?);
+        Ok(())
+    }
+
+    pub fn connect(device: &str, connect: bool) -> anyhow::Result<()> {
+        let bus = System::bus()?;
+        let objects = Self::get_objects(bus)?;
+        let (path, name) = objects
+            .iter()
+            .filter_map(|(path, interfaces)| Some((path, interfaces.get(BLUEZ_DEVICE)?)))
+            .find(|(_, properties)| {
+                [
+                    Self::get::<String>(properties, "Alias"),
+                    Self::get(properties, "Address"),
+                ]
+                .into_iter()
+                .flatten()
+                .any(|id| id.eq_ignore_ascii_case(device))
+            })
+            .map(|(path, properties)| (path, Self::name(properties)))
+            .ok_or(anyhow!("Device '{device}' not found"))?;
+        let proxy = Self::proxy(bus, path.as_str(), BLUEZ_DEVICE)?;
+        let (method, success, failure) = if connect {
+            ("Connect", "Connected to", "Failed to connect to")
+        } else {
+            (
+                "Disconnect",
+                "Disconnected from",
+                "Failed to disconnect from",
+            )
+        };
+        let notifier = Notifier::new("bluetooth");
+        match proxy.call_method(method, &()) {
+            Ok(_) => notifier.send("Bluetooth", &format!("{success} '{name}'"), None, None),
+            Err(e) => {
+                let body = format!("{failure} '{name}'");
+                notifier.send("Bluetooth", &body, Some(Urgency::Critical), None)?;
+                Err(anyhow!("{body}: {e}"))
+            }
+        }
+    }
+
+    pub fn handle(operation: BluetoothOp) -> anyhow::Result<()> {
+        match operation {
+            BluetoothOp::Power { operation } => Self::power(operation),
+            BluetoothOp::List => Self::list(),
+            BluetoothOp::Connect { device } => Self::connect(&device, true),
+            BluetoothOp::Disconnect { device } => Self::connect(&device, false),
+        }
+    }
+
+    fn update(state: BluetoothState, current: &mut Option<BluetoothState>) -> anyhow::Result<()> {
+        if Some(&state) != current.as_ref() {
+            if let Some(prev) = current.take() {
+                state.notify(prev)?;
+            }
+            state.log()?;
+            *current = Some(state);
+        }
+        Ok(())
+    }
+
+    fn get_state(bus: &Connection) -> anyhow::Result<BluetoothState> {
+        let objects = Self::get_objects(bus)?;
+        let powered = Self::get_adapter(&objects).is_ok_and(|(_, powered)| powered);
+        let mut devices = objects
+            .values()
+            .filter_map(|interfaces| {
+                let properties = interfaces.get(BLUEZ_DEVICE)?;
+                let battery = interfaces
+                    .get(BLUEZ_BATTERY)
+                    .and_then(|battery| Self::get(battery, "Percentage"));
+                Some(BluetoothDevice::new(properties, battery))
+            })
+            .collect::<Vec<BluetoothDevice>>();
+        devices.sort_by(|a, b| b.connected.cmp(&a.connected).then(a.name.cmp(&b.name)));
+        Ok(BluetoothState::new(powered, devices))
+    }
+
+    fn get_objects(bus: &Connection) -> anyhow::Result<Objects> {
+        let objects = Self::proxy(bus, "/", OBJECT_MANAGER)?.call("GetManagedObjects", &())?;
+        Ok(objects)
+    }
+
+    fn get_adapter(objects: &Objects) -> anyhow::Result<(&OwnedObjectPath, bool)> {
+        objects
+            .iter()
+            .find_map(|(path, interfaces)| {
+                let properties = interfaces.get(BLUEZ_ADAPTER)?;
+                Some((path, Self::get(properties, "Powered").unwrap_or(false)))
+            })
+            .ok_or(anyhow!("No bluetooth adapter found"))
+    }
+
+    fn get<T: TryFrom<OwnedValue>>(
+        properties: &HashMap<String, OwnedValue>,
+        key: &str,
+    ) -> Option<T> {
+        properties
+            .get(key)
+            .and_then(|value| T::try_from(value.clone()).ok())
+    }
+
+    fn name(properties: &HashMap<String, OwnedValue>) -> String {
+        Self::get(properties, "Alias")
+            .or(Self::get(properties, "Address"))
+            .unwrap_or_default()
+    }
+
+    fn proxy<'a>(bus: &Connection, path: &'a str, interface: &'a str) -> anyhow::Result<Proxy<'a>> {
+        Ok(ProxyBuilder::new_bare(bus)
+            .destination(BLUEZ)?
+            .path(path)?
+            .interface(interface)?
+            .cache_properties(CacheProperties::No)
+            .build()?)
+    }
+}
+
+#[derive(Clone, Subcommand)]
+pub enum BluetoothOp {
+    /// Turn adapter on or off
+    Power {
+        #[arg(value_enum)]
+        operation: BluetoothPowerOp,
+    },
+    /// List devices
+    List,
+    /// Connect to a device by name or address
+    Connect { device: String },
+    /// Disconnect from a device by name or address
+    Disconnect { device: String },
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum BluetoothPowerOp {
+    On,
+    Off,
+    Toggle,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct BluetoothDevice {
+    name: String,
+    address: String,
+    paired: bool,
+    connected: bool,
+    battery: Option<u8>,
+    icon: String,
+}
+
+impl BluetoothDevice {
+    pub fn new(properties: &HashMap<String, OwnedValue>, battery: Option<u8>) -> Self {
+        let icon = match Bluetooth::get::<String>(properties, "Icon").as_deref() {
+            Some("audio-headset" | "audio-headphones") => "󰋋 ",
+            Some("audio-card") => "󰓃 ",
+            Some("input-mouse") => "󰍽 ",
+            Some("input-keyboard") => "󰌌 ",
+            Some("input-gaming") => "󰊴 ",
+            Some("phone") => "󰏲 ",
+            _ => "󰂯 ",
+        }
+        .to_string();
+        Self {
+            name: Bluetooth::name(properties),
+            address: Bluetooth::get(properties, "Address").unwrap_or_default(),
+            paired: Bluetooth::get(properties, "Paired").unwrap_or(false),
+            connected: Bluetooth::get(properties, "Connected").unwrap_or(false),
+            battery,
+            icon,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct BluetoothState {
+    powered: bool,
+    connected: usize,
+    devices: Vec<BluetoothDevice>,
+    icon: String,
+}
+
+impl BluetoothState {
+    pub fn new(powered: bool, devices: Vec<BluetoothDevice>) -> Self {
+        let connected = devices.iter().filter(|device| device.connected).count();
+        let icon = if !powered {
+            "󰂲 "
+        } else if connected > 0 {
+            "󰂱 "
+        } else {
+            "󰂯 "
+        }
+        .to_string();
+        Self {
+            powered,
+            connected,
+            devices,
+            icon,
+        }
+    }
+
+    pub fn notify(&self, prev: Self) -> anyhow::Result<()> {
+        let notifier = Notifier::new("bluetooth");
+        let connected = |state: &Self, address: &str| {
+            state
+                .devices
+                .iter()
+                .any(|device| device.address == address && device.connected)
+        };
+        for device in self.devices.iter().filter(|d| d.connected) {
+            if !connected(&prev, &device.address) {
+                let body = format!("Connected to '{}'", device.name);
+                notifier.send("Bluetooth", &body, None, None)?;
+            }
+        }
+        for device in prev.devices.iter().filter(|d| d.connected) {
+            if !connected(self, &device.address) {
+                let body = format!("Disconnected from '{}'", device.name);
+                notifier.send("Bluetooth", &body, None, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn log(&self) -> anyhow::Result<()> {
+        Logger::new("bluetooth").write(self)
+    }
+}
@@ -0,0 +1,304 @@
+use crate::config::{ColorMode, Config, LayoutConfig};
+use crate::hypr::Hypr;
+use crate::inhibit::Inhibit;
+use crate::logger::Logger;
+use crate::notifier::Notifier;
+use crate::sway::Sway;
+use anyhow::anyhow;
+use clap::Subcommand;
+use rand::seq::IteratorRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+// what the eww config needs from a window manager, states are logged
+// under the same names whatever the backend
+pub trait Compositor {
+    fn running(&self) -> bool;
+
+    // returns when the compositor goes away
+    fn listen(&self) -> anyhow::Result<()>;
+
+    fn get_workspaces(&self) -> anyhow::Result<Vec<WorkspaceState>>;
+
+    fn get_workspace(&self) -> anyhow::Result<i32>;
+
+    fn get_window(&self) -> anyhow::Result<WindowState>;
+
+    fn get_layout(&self) -> anyhow::Result<LayoutConfig>;
+
+    fn set_layout(&self, layout: &LayoutConfig) -> anyhow::Result<()>;
+
+    fn get_color(&self) -> String;
+
+    fn set_color(&self, colors: &[String], angle: u32) -> anyhow::Result<()>;
+
+    fn change_workspace(&self) -> anyhow::Result<()> {
+        let config = Config::read()?.compositor;
+        let mut states = self.get_workspaces()?;
+        let mut monitors = states
+            .iter()
+            .map(|state| state.monitor.clone())
+            .collect::<Vec<String>>();
+        monitors.sort();
+        monitors.dedup();
+        for monitor in &monitors {
+            // a single monitor keeps the historical 1..=5 range
+            let range = match config.workspaces.get(monitor) {
+                Some(range) => Some(*range),
+                None if monitors.len() == 1 => Some([1, 5]),
+                None => None,
+            };
+            if let Some([start, end]) = range {
+                for id in start..=end {
+                    if states.iter().all(|state| state.id != id) {
+                        states.push(WorkspaceState::new(id, monitor, false, false, Vec::new()));
+                    }
+                }
+            }
+        }
+        states.sort_by_key(|workspace| workspace.id);
+        for monitor in &monitors {
+            let own = states
+                .iter()
+                .filter(|state| state.monitor == *monitor)
+                .cloned()
+                .collect::<Vec<WorkspaceState>>();
+            Logger::new(&format!("workspaces.{monitor}")).write(&own)?;
+        }
+        Logger::new("workspaces").write(&states)
+    }
+
+    fn change_window(&self) -> anyhow::Result<()> {
+        let window = self.get_window()?;
+        Inhibit::focus(&window.class, window.fullscreen)?;
+        Logger::new("activewindow").write(&window)
+    }
+
+    fn change_color(&self, class: &str) -> anyhow::Result<()> {
+        let config = Config::read()?.compositor.colors;
+        let palette = &config.palette;
+        if palette.is_empty() {
+            return Ok(());
+        }
+        let current = self.get_color();
+        let next = palette
+            .iter()
+            .position(|color| *color == current)
+            .map_or(0, |i| (i + 1) % palette.len());
+        let colors = match config.mode {
+            ColorMode::Random => {
+                let color = palette
+                    .iter()
+                    .filter(|color| **color != current)
+                    .choose(&mut rand::thread_rng());
+                vec![color.unwrap_or(&palette[0]).clone()]
+            }
+            ColorMode::Sequential => vec![palette[next].clone()],
+            ColorMode::Workspace => {
+                let id = self.get_workspace()?;
+                let color = config.workspaces.get(&id.to_string()).cloned();
+                let index = (id - 1).rem_euclid(palette.len() as i32) as usize;
+                vec![color.unwrap_or(palette[index].clone())]
+            }
+            ColorMode::Class => {
+                let color = config.classes.get(class).cloned();
+                let hash = class.bytes().map(|b| b as usize).sum::<usize>();
+                vec![color.unwrap_or(palette[hash % palette.len()].clone())]
+            }
+            ColorMode::Gradient => (0..config.gradient.max(1))
+                .map(|i| palette[(next + i) % palette.len()].clone())
+                .collect(),
+        };
+        self.set_color(&colors, config.angle)?;
+        Logger::new("color").write(&colors[0])
+    }
+
+    fn change_layout(&self, operation: LayoutOp) -> anyhow::Result<()> {
+        let layouts = Config::read()?.compositor.layouts;
+        let current = self.get_layout()?;
+        let index = layouts.iter().position(|layout| {
+            layout.layout == current.layout && layout.variant == current.variant
+        });
+        let index = match operation {
+            LayoutOp::List => {
+                let states = layouts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, layout)| LayoutState::new(layout, "", Some(i) == index))
+                    .collect::<Vec<LayoutState>>();
+                println!("{}", serde_json::to_string(&states)?);
+                return Ok(());
+            }
+            LayoutOp::Next => index.map_or(0, |i| (i + 1) % layouts.len()),
+            LayoutOp::Prev => index.map_or(0, |i| (i + layouts.len() - 1) % layouts.len()),
+            LayoutOp::Set { layout } => layouts
+                .iter()
+                .position(|l| l.to_string() == layout || l.layout == layout)
+                .ok_or(anyhow!("Layout '{layout}' is not configured"))?,
+        };
+        let layout = layouts.get(index).ok_or(anyhow!("No layout configured"))?;
+        self.set_layout(layout)?;
+        self.update_layout("")
+    }
+
+    fn update_layout(&self, name: &str) -> anyhow::Result<()> {
+        let logger = Logger::<LayoutState>::new("layout");
+        let state = LayoutState::new(&self.get_layout()?, name, true);
+        let prev = logger.read().ok();
+        if prev.as_ref().map(|prev| &prev.code) != Some(&state.code) {
+            state.notify()?;
+        }
+        if prev.as_ref() != Some(&state) {
+            logger.write(&state)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn detect() -> Option<Box<dyn Compositor>> {
+    let compositors: [Box<dyn Compositor>; 2] = [Box::new(Hypr::new()), Box::new(Sway::new())];
+    compositors
+        .into_iter()
+        .find(|compositor| compositor.running())
+}
+
+pub fn current() -> anyhow::Result<Box<dyn Compositor>> {
+    detect().ok_or(anyhow!("No supported compositor is running"))
+}
+
+pub fn listen() -> anyhow::Result<()> {
+    loop {
+        if let Some(compositor) = detect() {
+            match compositor.listen() {
+                Ok(_) => eprintln!("Compositor socket closed, reconnecting"),
+                Err(e) => eprintln!("Compositor socket failed ({e}), reconnecting"),
+            }
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WorkspaceState {
+    pub id: i32,
+    pub monitor: String,
+    pub windows: u16,
+    pub active: bool,
+    pub visible: bool,
+    pub clients: Vec<ClientState>,
+}
+
+impl WorkspaceState {
+    pub fn new(
+        id: i32,
+        monitor: &str,
+        active: bool,
+        visible: bool,
+        clients: Vec<ClientState>,
+    ) -> Self {
+        let clients = clients
+            .into_iter()
+            .filter(|client| client.workspace == id)
+            .collect::<Vec<ClientState>>();
+        Self {
+            id,
+            monitor: monitor.to_string(),
+            windows: clients.len() as u16,
+            active,
+            visible,
+            clients,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClientState {
+    pub address: String,
+    pub workspace: i32,
+    pub class: String,
+    pub title: String,
+    pub pid: i32,
+    pub urgent: bool,
+    pub fullscreen: bool,
+    pub icon: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WindowState {
+    class: String,
+    title: String,
+    fullscreen: bool,
+    icon: String,
+}
+
+impl WindowState {
+    pub fn new(
+        class: &str,
+        title: &str,
+        fullscreen: bool,
+        icons: &HashMap<String, String>,
+    ) -> Self {
+        Self {
+            class: class.to_string(),
+            title: title.to_string(),
+            fullscreen,
+            icon: Self::icon(class, icons),
+        }
+    }
+
+    pub fn icon(class: &str, icons: &HashMap<String, String>) -> String {
+        icons
+            .get(class)
+            .or(icons.get("default"))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Subcommand)]
+pub enum LayoutOp {
+    /// Switch to next layout
+    Next,
+    /// Switch to previous layout
+    Prev,
+    /// List configured layouts
+    List,
+    /// Switch to a layout, e.g. 'fr' or 'us(intl)'
+    Set { layout: String },
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct LayoutState {
+    layout: String,
+    variant: String,
+    code: String,
+    name: String,
+    active: bool,
+}
+
+impl LayoutState {
+    pub fn new(layout: &LayoutConfig, name: &str, active: bool) -> Self {
+        let code = layout.to_string();
+        let name = match name {
+            "" => code.clone(),
+            _ => name.to_string(),
+        };
+        Self {
+            layout: layout.layout.clone(),
+            variant: layout.variant.clone(),
+            code,
+            name,
+            active,
+        }
+    }
+
+    pub fn notify(&self) -> anyhow::Result<()> {
+        Notifier::new("layout").send(
+            "Keyboard layout",
+            &format!("Set to '{}'", self.name),
+            None,
+            None,
+        )
+    }
+}
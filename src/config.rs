@@ -0,0 +1,228 @@
+use crate::system::SystemAction;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+
+const FILE: &str = "/home/emanuel/.config/emanager/config.json";
+const COLORS: &[&str] = &["7aa2f7", "9ece6a", "e0af68", "bb9af7", "7dcfff", "c0caf5"];
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub power: PowerConfig,
+    pub compositor: CompositorConfig,
+    pub hypr: HyprConfig,
+    pub inhibit: InhibitConfig,
+    pub acpi: AcpiConfig,
+    pub schedule: ScheduleConfig,
+}
+
+impl Config {
+    pub fn read() -> anyhow::Result<Self> {
+        match std::fs::read_to_string(FILE) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct PowerConfig {
+    pub ac: Profile,
+    pub battery: Profile,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct AcpiConfig {
+    // actions run when closing the lid and pressing the sleep button
+    pub lid: SystemAction,
+    pub sleep: SystemAction,
+}
+
+impl Default for AcpiConfig {
+    fn default() -> Self {
+        Self {
+            lid: SystemAction::Suspend,
+            sleep: SystemAction::Suspend,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    // seconds before a scheduled action at which to warn
+    pub warnings: Vec<u64>,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            warnings: vec![600, 300, 60],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct InhibitConfig {
+    // inhibit idle while the focused window is fullscreen
+    pub fullscreen: bool,
+    // inhibit idle while an MPRIS player is playing
+    pub media: bool,
+    // classes that inhibit whenever focused, and classes or players that never do
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl Default for InhibitConfig {
+    fn default() -> Self {
+        Self {
+            fullscreen: true,
+            media: true,
+            allow: Vec::new(),
+            deny: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Profile {
+    pub brightness: Option<u32>,
+    pub idle: Option<Idle>,
+    pub profile: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct Idle {
+    pub lock: u32,
+    pub suspend: u32,
+}
+
+// settings shared by every compositor
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct CompositorConfig {
+    // first and last workspace shown on each monitor
+    pub workspaces: HashMap<String, [i32; 2]>,
+    // icon for each window class, 'default' for the others
+    pub icons: HashMap<String, String>,
+    pub layouts: Vec<LayoutConfig>,
+    pub colors: ColorConfig,
+}
+
+impl Default for CompositorConfig {
+    fn default() -> Self {
+        Self {
+            workspaces: HashMap::new(),
+            icons: HashMap::new(),
+            layouts: vec![LayoutConfig::new("fr"), LayoutConfig::new("us")],
+            colors: ColorConfig::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct HyprConfig {
+    // remember the layout of each window or class and restore it on focus
+    pub layout_memory: LayoutMemory,
+    // layout given to newly opened windows, e.g. 'us(intl)'
+    pub default_layout: Option<String>,
+    // special workspaces toggled with 'emanager scratch <name>'
+    pub scratchpads: HashMap<String, ScratchpadConfig>,
+    // list the binds of a submap in a notification when entering it
+    pub submap_binds: bool,
+}
+
+impl Default for HyprConfig {
+    fn default() -> Self {
+        Self {
+            layout_memory: LayoutMemory::Off,
+            default_layout: None,
+            scratchpads: HashMap::new(),
+            submap_binds: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ScratchpadConfig {
+    pub class: String,
+    pub command: String,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutMemory {
+    Off,
+    Window,
+    Class,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct LayoutConfig {
+    pub layout: String,
+    #[serde(default)]
+    pub variant: String,
+    #[serde(default)]
+    pub options: String,
+}
+
+impl LayoutConfig {
+    pub fn new(layout: &str) -> Self {
+        Self {
+            layout: layout.to_string(),
+            variant: String::new(),
+            options: String::new(),
+        }
+    }
+}
+
+impl Display for LayoutConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.variant.as_str() {
+            "" => write!(f, "{}", self.layout),
+            variant => write!(f, "{}({variant})", self.layout),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ColorConfig {
+    pub mode: ColorMode,
+    pub palette: Vec<String>,
+    // fixed color for each workspace id or window class
+    pub workspaces: HashMap<String, String>,
+    pub classes: HashMap<String, String>,
+    // number of colors and angle of the border gradient
+    pub gradient: usize,
+    pub angle: u32,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        Self {
+            mode: ColorMode::Random,
+            palette: COLORS.iter().map(|color| color.to_string()).collect(),
+            workspaces: HashMap::new(),
+            classes: HashMap::new(),
+            gradient: 2,
+            angle: 45,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    Random,
+    Sequential,
+    Workspace,
+    Class,
+    Gradient,
+}
@@ -0,0 +1,503 @@
+// events sent by Hyprland on its socket2, one per line as 'name>>data'
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Workspace {
+        name: String,
+    },
+    WorkspaceV2 {
+        id: i32,
+        name: String,
+    },
+    FocusedMonitor {
+        monitor: String,
+        workspace: String,
+    },
+    FocusedMonitorV2 {
+        monitor: String,
+        id: i32,
+    },
+    ActiveWindow {
+        class: String,
+        title: String,
+    },
+    ActiveWindowV2 {
+        address: Option<String>,
+    },
+    Fullscreen {
+        fullscreen: bool,
+    },
+    MonitorAdded {
+        monitor: String,
+    },
+    MonitorAddedV2 {
+        id: i32,
+        monitor: String,
+        description: String,
+    },
+    MonitorRemoved {
+        monitor: String,
+    },
+    MonitorRemovedV2 {
+        id: i32,
+        monitor: String,
+        description: String,
+    },
+    CreateWorkspace {
+        name: String,
+    },
+    CreateWorkspaceV2 {
+        id: i32,
+        name: String,
+    },
+    DestroyWorkspace {
+        name: String,
+    },
+    DestroyWorkspaceV2 {
+        id: i32,
+        name: String,
+    },
+    MoveWorkspace {
+        workspace: String,
+        monitor: String,
+    },
+    MoveWorkspaceV2 {
+        id: i32,
+        name: String,
+        monitor: String,
+    },
+    RenameWorkspace {
+        id: i32,
+        name: String,
+    },
+    ActiveSpecial {
+        workspace: String,
+        monitor: String,
+    },
+    ActiveSpecialV2 {
+        id: i32,
+        name: String,
+        monitor: String,
+    },
+    ActiveLayout {
+        keyboard: String,
+        layout: String,
+    },
+    OpenWindow {
+        address: String,
+        workspace: String,
+        class: String,
+        title: String,
+    },
+    CloseWindow {
+        address: String,
+    },
+    MoveWindow {
+        address: String,
+        workspace: String,
+    },
+    MoveWindowV2 {
+        address: String,
+        id: i32,
+        workspace: String,
+    },
+    WindowTitle {
+        address: String,
+    },
+    WindowTitleV2 {
+        address: String,
+        title: String,
+    },
+    OpenLayer {
+        namespace: String,
+    },
+    CloseLayer {
+        namespace: String,
+    },
+    Submap {
+        name: String,
+    },
+    ChangeFloatingMode {
+        address: String,
+        floating: bool,
+    },
+    Urgent {
+        address: String,
+    },
+    Bell {
+        address: String,
+    },
+    Minimize {
+        address: String,
+        minimized: bool,
+    },
+    Screencast {
+        active: bool,
+        owner: String,
+    },
+    Pin {
+        address: String,
+        pinned: bool,
+    },
+    ToggleGroup {
+        open: bool,
+        addresses: Vec<String>,
+    },
+    MoveIntoGroup {
+        address: String,
+    },
+    MoveOutOfGroup {
+        address: String,
+    },
+    LockGroups {
+        locked: bool,
+    },
+    IgnoreGroupLock {
+        ignored: bool,
+    },
+    ConfigReloaded,
+    Unknown {
+        name: String,
+        data: String,
+    },
+}
+
+impl From<&str> for Event {
+    fn from(line: &str) -> Self {
+        let (name, data) = line.split_once(">>").unwrap_or((line, ""));
+        let flag = |value: &str| value == "1";
+        match name {
+            "workspace" => Self::Workspace {
+                name: data.to_string(),
+            },
+            "workspacev2" => {
+                let [id, name] = fields(data);
+                Self::WorkspaceV2 {
+                    id: id.parse().unwrap_or_default(),
+                    name,
+                }
+            }
+            "focusedmon" => {
+                let [monitor, workspace] = fields(data);
+                Self::FocusedMonitor { monitor, workspace }
+            }
+            "focusedmonv2" => {
+                let [monitor, id] = fields(data);
+                Self::FocusedMonitorV2 {
+                    monitor,
+                    id: id.parse().unwrap_or_default(),
+                }
+            }
+            "activewindow" => {
+                let [class, title] = fields(data);
+                Self::ActiveWindow { class, title }
+            }
+            "activewindowv2" => Self::ActiveWindowV2 {
+                address: match data {
+                    "" | "," => None,
+                    _ => Some(data.to_string()),
+                },
+            },
+            "fullscreen" => Self::Fullscreen {
+                fullscreen: flag(data),
+            },
+            "monitoradded" => Self::MonitorAdded {
+                monitor: data.to_string(),
+            },
+            "monitoraddedv2" => {
+                let [id, monitor, description] = fields(data);
+                Self::MonitorAddedV2 {
+                    id: id.parse().unwrap_or_default(),
+                    monitor,
+                    description,
+                }
+            }
+            "monitorremoved" => Self::MonitorRemoved {
+                monitor: data.to_string(),
+            },
+            "monitorremovedv2" => {
+                let [id, monitor, description] = fields(data);
+                Self::MonitorRemovedV2 {
+                    id: id.parse().unwrap_or_default(),
+                    monitor,
+                    description,
+                }
+            }
+            "createworkspace" => Self::CreateWorkspace {
+                name: data.to_string(),
+            },
+            "createworkspacev2" => {
+                let [id, name] = fields(data);
+                Self::CreateWorkspaceV2 {
+                    id: id.parse().unwrap_or_default(),
+                    name,
+                }
+            }
+            "destroyworkspace" => Self::DestroyWorkspace {
+                name: data.to_string(),
+            },
+            "destroyworkspacev2" => {
+                let [id, name] = fields(data);
+                Self::DestroyWorkspaceV2 {
+                    id: id.parse().unwrap_or_default(),
+                    name,
+                }
+            }
+            "moveworkspace" => {
+                let [workspace, monitor] = fields(data);
+                Self::MoveWorkspace { workspace, monitor }
+            }
+            "moveworkspacev2" => {
+                let [id, name, monitor] = fields(data);
+                Self::MoveWorkspaceV2 {
+                    id: id.parse().unwrap_or_default(),
+                    name,
+                    monitor,
+                }
+            }
+            "renameworkspace" => {
+                let [id, name] = fields(data);
+                Self::RenameWorkspace {
+                    id: id.parse().unwrap_or_default(),
+                    name,
+                }
+            }
+            "activespecial" => {
+                let [workspace, monitor] = fields(data);
+                Self::ActiveSpecial { workspace, monitor }
+            }
+            "activespecialv2" => {
+                let [id, name, monitor] = fields(data);
+                Self::ActiveSpecialV2 {
+                    id: id.parse().unwrap_or_default(),
+                    name,
+                    monitor,
+                }
+            }
+            "activelayout" => {
+                let [keyboard, layout] = fields(data);
+                Self::ActiveLayout { keyboard, layout }
+            }
+            "openwindow" => {
+                let [address, workspace, class, title] = fields(data);
+                Self::OpenWindow {
+                    address,
+                    workspace,
+                    class,
+                    title,
+                }
+            }
+            "closewindow" => Self::CloseWindow {
+                address: data.to_string(),
+            },
+            "movewindow" => {
+                let [address, workspace] = fields(data);
+                Self::MoveWindow { address, workspace }
+            }
+            "movewindowv2" => {
+                let [address, id, workspace] = fields(data);
+                Self::MoveWindowV2 {
+                    address,
+                    id: id.parse().unwrap_or_default(),
+                    workspace,
+                }
+            }
+            "windowtitle" => Self::WindowTitle {
+                address: data.to_string(),
+            },
+            "windowtitlev2" => {
+                let [address, title] = fields(data);
+                Self::WindowTitleV2 { address, title }
+            }
+            "openlayer" => Self::OpenLayer {
+                namespace: data.to_string(),
+            },
+            "closelayer" => Self::CloseLayer {
+                namespace: data.to_string(),
+            },
+            "submap" => Self::Submap {
+                name: data.to_string(),
+            },
+            "changefloatingmode" => {
+                let [address, floating] = fields(data);
+                Self::ChangeFloatingMode {
+                    address,
+                    floating: flag(&floating),
+                }
+            }
+            "urgent" => Self::Urgent {
+                address: data.to_string(),
+            },
+            "bell" => Self::Bell {
+                address: data.to_string(),
+            },
+            "minimize" => {
+                let [address, minimized] = fields(data);
+                Self::Minimize {
+                    address,
+                    minimized: flag(&minimized),
+                }
+            }
+            "screencast" => {
+                let [active, owner] = fields(data);
+                Self::Screencast {
+                    active: flag(&active),
+                    owner,
+                }
+            }
+            "pin" => {
+                let [address, pinned] = fields(data);
+                Self::Pin {
+                    address,
+                    pinned: flag(&pinned),
+                }
+            }
+            "togglegroup" => {
+                let mut fields = data.split(',').map(String::from);
+                Self::ToggleGroup {
+                    open: fields.next().is_some_and(|open| flag(&open)),
+                    addresses: fields.collect(),
+                }
+            }
+            "moveintogroup" => Self::MoveIntoGroup {
+                address: data.to_string(),
+            },
+            "moveoutofgroup" => Self::MoveOutOfGroup {
+                address: data.to_string(),
+            },
+            "lockgroups" => Self::LockGroups { locked: flag(data) },
+            "ignoregrouplock" => Self::IgnoreGroupLock {
+                ignored: flag(data),
+            },
+            "configreloaded" => Self::ConfigReloaded,
+            _ => Self::Unknown {
+                name: name.to_string(),
+                data: data.to_string(),
+            },
+        }
+    }
+}
+
+// the last field may contain commas, e.g. window titles
+fn fields<const N: usize>(data: &str) -> [String; N] {
+    let mut fields = data.splitn(N, ',');
+    std::array::from_fn(|_| fields.next().unwrap_or("").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_keep_their_commas() {
+        assert_eq!(
+            Event::from("activewindow>>firefox,Hello, world, again"),
+            Event::ActiveWindow {
+                class: "firefox".to_string(),
+                title: "Hello, world, again".to_string(),
+            }
+        );
+        assert_eq!(
+            Event::from("openwindow>>5d3a9f0,2,kitty,vim a,b.rs"),
+            Event::OpenWindow {
+                address: "5d3a9f0".to_string(),
+                workspace: "2".to_string(),
+                class: "kitty".to_string(),
+                title: "vim a,b.rs".to_string(),
+            }
+        );
+        assert_eq!(
+            Event::from("windowtitlev2>>5d3a9f0,a, b"),
+            Event::WindowTitleV2 {
+                address: "5d3a9f0".to_string(),
+                title: "a, b".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn empty_active_window() {
+        for line in ["activewindowv2>>,", "activewindowv2>>"] {
+            assert_eq!(Event::from(line), Event::ActiveWindowV2 { address: None });
+        }
+        assert_eq!(
+            Event::from("activewindowv2>>5d3a9f0"),
+            Event::ActiveWindowV2 {
+                address: Some("5d3a9f0".to_string()),
+            }
+        );
+        assert_eq!(
+            Event::from("activewindow>>,"),
+            Event::ActiveWindow {
+                class: String::new(),
+                title: String::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn v2_events() {
+        assert_eq!(
+            Event::from("focusedmonv2>>DP-1,3"),
+            Event::FocusedMonitorV2 {
+                monitor: "DP-1".to_string(),
+                id: 3,
+            }
+        );
+        assert_eq!(
+            Event::from("movewindowv2>>5d3a9f0,-98,special:term"),
+            Event::MoveWindowV2 {
+                address: "5d3a9f0".to_string(),
+                id: -98,
+                workspace: "special:term".to_string(),
+            }
+        );
+        assert_eq!(
+            Event::from("moveworkspacev2>>4,4,HDMI-A-1"),
+            Event::MoveWorkspaceV2 {
+                id: 4,
+                name: "4".to_string(),
+                monitor: "HDMI-A-1".to_string(),
+            }
+        );
+        assert_eq!(
+            Event::from("monitoraddedv2>>1,DP-2,Dell Inc. DELL U2720Q, 27in"),
+            Event::MonitorAddedV2 {
+                id: 1,
+                monitor: "DP-2".to_string(),
+                description: "Dell Inc. DELL U2720Q, 27in".to_string(),
+            }
+        );
+        assert_eq!(
+            Event::from("activespecialv2>>-98,special:term,eDP-1"),
+            Event::ActiveSpecialV2 {
+                id: -98,
+                name: "special:term".to_string(),
+                monitor: "eDP-1".to_string(),
+            }
+        );
+        assert_eq!(
+            Event::from("bell>>5d3a9f0"),
+            Event::Bell {
+                address: "5d3a9f0".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_events() {
+        assert_eq!(
+            Event::from("somethingnew>>a,b"),
+            Event::Unknown {
+                name: "somethingnew".to_string(),
+                data: "a,b".to_string(),
+            }
+        );
+        assert_eq!(
+            Event::from("garbage"),
+            Event::Unknown {
+                name: "garbage".to_string(),
+                data: String::new(),
+            }
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use crate::logger::{self, DIR};
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+const SIZE: u64 = 2_u64.pow(20);
+const ROTATE: u32 = 8;
+
+pub struct History<T: Serialize + for<'a> Deserialize<'a>> {
+    file: String,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Serialize + for<'a> Deserialize<'a>> History<T> {
+    pub fn new(name: &str) -> Self {
+        Self {
+            file: format!("{DIR}/{name}.history"),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn push(&self, sample: &T) -> anyhow::Result<()> {
+        std::fs::create_dir_all(DIR)?;
+        self.rotate()?;
+        logger::append(&self.file, sample)
+    }
+
+    pub fn read(&self) -> anyhow::Result<Vec<T>> {
+        let mut samples = Vec::new();
+        for index in (0..ROTATE).rev() {
+            if let Ok(content) = std::fs::read_to_string(self.path(index)) {
+                samples.extend(content.lines().flat_map(|line| serde_json::from_str(line)));
+            }
+        }
+        Ok(samples)
+    }
+
+    fn rotate(&self) -> anyhow::Result<()> {
+        if std::fs::metadata(&self.file).is_ok_and(|metadata| metadata.len() > SIZE) {
+            for index in (1..ROTATE).rev() {
+                let from = self.path(index - 1);
+                if std::path::Path::new(&from).exists() {
+                    std::fs::rename(from, self.path(index))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, index: u32) -> String {
+        match index {
+            0 => self.file.clone(),
+            _ => format!("{}.{index}", self.file),
+        }
+    }
+}
@@ -1,141 +1,854 @@
+use crate::compositor::{ClientState, Compositor, WindowState, WorkspaceState};
+use crate::config::{ColorMode, Config, LayoutConfig, LayoutMemory, ScratchpadConfig};
+use crate::event::Event;
 use crate::logger::Logger;
-use clap::ValueEnum;
-use hyprland::data::{Client, Workspace, Workspaces};
-use hyprland::keyword::Keyword;
-use hyprland::shared::{HyprData, HyprDataActive, HyprDataActiveOptional};
-use rand::seq::IteratorRandom;
-use serde::{Deserialize, Serialize};
-use std::fmt::Display;
-use std::io::{BufRead, BufReader};
+use crate::notifier::Notifier;
+use anyhow::anyhow;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::os::unix::net::UnixStream;
-use std::process::Command;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
 
-const COLORS: &[&str] = &["7aa2f7", "9ece6a", "e0af68", "bb9af7", "7dcfff", "c0caf5"];
-
-pub struct Hypr;
+pub struct Hypr {
+    // the directory holding the sockets of the instance found on creation
+    instance: Option<PathBuf>,
+    // Hyprland only reports urgency through events
+    urgent: RefCell<HashSet<String>>,
+}
 
 impl Hypr {
-    pub fn listen() -> anyhow::Result<()> {
-        while !Self::running() {
-            std::thread::sleep(Duration::from_secs(1));
+    pub fn new() -> Self {
+        Self {
+            instance: Self::instance(),
+            urgent: RefCell::new(HashSet::new()),
+        }
+    }
+
+    pub fn scratch(&self, name: &str) -> anyhow::Result<()> {
+        if !self.running() {
+            return Err(anyhow!("Scratchpads need Hyprland"));
+        }
+        let scratchpads = Config::read()?.hypr.scratchpads;
+        let scratchpad = scratchpads
+            .get(name)
+            .ok_or(anyhow!("Scratchpad '{name}' is not configured"))?;
+        self.toggle_scratchpad(name, scratchpad)
+    }
+
+    fn toggle_scratchpad(&self, name: &str, scratchpad: &ScratchpadConfig) -> anyhow::Result<()> {
+        let special = format!("special:{name}");
+        let clients = self.query::<Vec<HyprClient>>("clients")?;
+        // another window of the same class must not replace the one already there
+        let class = |client: &&HyprClient| client.class == scratchpad.class;
+        match clients
+            .iter()
+            .filter(class)
+            .find(|client| client.workspace.name == special)
+            .or_else(|| clients.iter().find(class))
+        {
+            Some(client) if client.workspace.name != special => self.dispatch(&format!(
+                "movetoworkspacesilent {special},address:{}",
+                client.address
+            ))?,
+            Some(_) => (),
+            None => self.dispatch(&format!(
+                "exec [workspace {special} silent] {}",
+                scratchpad.command
+            ))?,
+        }
+        self.dispatch(&format!("togglespecialworkspace {name}"))
+    }
+
+    // specials maps each monitor to the special workspace it shows
+    fn change_scratchpads(&self, specials: &HashMap<String, String>) -> anyhow::Result<()> {
+        let scratchpads = Config::read()?.hypr.scratchpads;
+        let clients = self.query::<Vec<HyprClient>>("clients")?;
+        let mut states = scratchpads
+            .iter()
+            .map(|(name, scratchpad)| ScratchpadState {
+                name: name.clone(),
+                class: scratchpad.class.clone(),
+                running: clients
+                    .iter()
+                    .any(|client| client.class == scratchpad.class),
+                visible: specials
+                    .values()
+                    .any(|special| special.strip_prefix("special:") == Some(name)),
+            })
+            .collect::<Vec<ScratchpadState>>();
+        states.sort_by(|a, b| a.name.cmp(&b.name));
+        Logger::new("scratchpads").write(&states)
+    }
+
+    fn change_submap(&self, name: &str) -> anyhow::Result<()> {
+        let binds = match name {
+            "" => Vec::new(),
+            _ => self
+                .query::<Vec<HyprBind>>("binds")?
+                .iter()
+                .filter(|bind| bind.submap == name)
+                .map(BindState::new)
+                .collect(),
+        };
+        let state = SubmapState {
+            name: name.to_string(),
+            active: !name.is_empty(),
+            binds,
+        };
+        if state.active && Config::read()?.hypr.submap_binds {
+            state.notify()?;
+        }
+        Logger::new("submap").write(&state)
+    }
+
+    // first color of a border, given as an ARGB int or as a gradient like
+    // 'rgba(7aa2f7ee) rgba(9ece6aee) 45deg' or 'ee7aa2f7 ee9ece6a 45deg'
+    fn parse_color(option: &HyprOption) -> Option<String> {
+        let text = [&option.custom, &option.str, &option.data]
+            .into_iter()
+            .flatten()
+            .find(|text| !text.trim().is_empty());
+        let color = match (text, option.int) {
+            (Some(gradient), _) => gradient.split_whitespace().next()?.to_lowercase(),
+            (None, Some(argb)) if argb != UNSET => format!("{:08x}", argb & 0xffffffff),
+            (None, _) => return None,
+        };
+        let hex = match color.split_once('(') {
+            Some((_, rgba)) => rgba.trim_end_matches(')').get(..6)?,
+            None => color.trim_start_matches("0x").get(2..8)?,
+        };
+        hex.chars()
+            .all(|c| c.is_ascii_hexdigit())
+            .then(|| hex.to_string())
+    }
+
+    fn get_active(&self) -> anyhow::Result<Option<HyprClient>> {
+        // an empty object when nothing is focused
+        let active = self.query::<serde_json::Value>("activewindow")?;
+        if active.as_object().is_some_and(|object| object.is_empty()) {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_value(active)?))
+    }
+
+    fn get_option(&self, name: &str) -> anyhow::Result<HyprOption> {
+        self.query(&format!("getoption {name}"))
+    }
+
+    fn keyword(&self, name: &str, value: &str) -> anyhow::Result<()> {
+        Self::check(self.request(&format!("keyword {name} {value}"))?)
+    }
+
+    fn dispatch(&self, dispatcher: &str) -> anyhow::Result<()> {
+        Self::check(self.request(&format!("dispatch {dispatcher}"))?)
+    }
+
+    fn check(reply: String) -> anyhow::Result<()> {
+        match reply.trim() {
+            "ok" | "" => Ok(()),
+            error => Err(anyhow!("Hyprland: {error}")),
+        }
+    }
+
+    fn query<T: DeserializeOwned>(&self, command: &str) -> anyhow::Result<T> {
+        Ok(serde_json::from_str(
+            &self.request(&format!("j/{command}"))?,
+        )?)
+    }
+
+    // loads every configured layout at once, then picks one of them
+    fn switch_layout(&self, layouts: &[LayoutConfig], index: usize) -> anyhow::Result<()> {
+        let list = |field: fn(&LayoutConfig) -> &str| {
+            layouts.iter().map(field).collect::<Vec<&str>>().join(",")
+        };
+        let (kb_layout, kb_variant) = (list(|l| &l.layout), list(|l| &l.variant));
+        let option = |name: &str| -> anyhow::Result<String> {
+            Ok(self.get_option(name)?.str.unwrap_or_default())
+        };
+        if option("input:kb_layout")? != kb_layout || option("input:kb_variant")? != kb_variant {
+            // only once, clearing variants first so no layout gets another's
+            self.keyword("input:kb_variant", "")?;
+            self.keyword("input:kb_layout", &kb_layout)?;
+            self.keyword("input:kb_variant", &kb_variant)?;
         }
+        let options = &layouts[index].options;
+        if option("input:kb_options")? != *options {
+            self.keyword("input:kb_options", options)?;
+        }
+        // a hyprctl command of its own, not a dispatcher
+        Self::check(self.request(&format!("switchxkblayout all {index}"))?)
+    }
+
+    // Hyprland answers on its command socket, then closes it
+    fn request(&self, command: &str) -> anyhow::Result<String> {
+        let mut stream = UnixStream::connect(self.socket(".socket.sock")?)?;
+        stream.write_all(command.as_bytes())?;
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        Ok(reply)
+    }
+
+    fn stream(&self) -> anyhow::Result<UnixStream> {
+        Ok(UnixStream::connect(self.socket(".socket2.sock")?)?)
+    }
+
+    fn socket(&self, name: &str) -> anyhow::Result<PathBuf> {
+        let instance = self
+            .instance
+            .as_ref()
+            .ok_or(anyhow!("No Hyprland instance found"))?;
+        Ok(instance.join(name))
+    }
+
+    // newer releases keep their sockets in $XDG_RUNTIME_DIR, older ones in /tmp
+    fn instance() -> Option<PathBuf> {
+        let mut roots = vec![PathBuf::from("/tmp/hypr")];
+        if let Ok(runtime) = std::env::var("XDG_RUNTIME_DIR") {
+            roots.insert(0, Path::new(&runtime).join("hypr"));
+        }
+        let alive = |dir: &Path| dir.join(".socket2.sock").exists();
+        if let Ok(signature) = std::env::var("HYPRLAND_INSTANCE_SIGNATURE") {
+            let instance = roots
+                .iter()
+                .map(|root| root.join(&signature))
+                .find(|dir| alive(dir));
+            if instance.is_some() {
+                return instance;
+            }
+        }
+        // the signature is stale or missing, e.g. after Hyprland restarted
+        roots
+            .iter()
+            .flat_map(std::fs::read_dir)
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|dir| alive(dir))
+            .max_by_key(|dir| dir.metadata().and_then(|meta| meta.modified()).ok())
+    }
+}
 
-        Self::change_workspace()?;
+impl Compositor for Hypr {
+    fn running(&self) -> bool {
+        self.stream().is_ok()
+    }
 
-        let stream = Self::stream()?;
+    fn listen(&self) -> anyhow::Result<()> {
+        let stream = self.stream()?;
         let reader = BufReader::new(stream);
-        let mut current = Self::get_active_address()?;
-        for line in reader.lines().flatten() {
-            let address = Self::get_active_address()?;
-            if line.starts_with("workspace") {
-                Self::change_workspace()?;
-            } else if line.starts_with("activewindowv2") && address != current {
-                Self::change_color()?;
-                current = address;
+        let mut memory = LayoutRecall::new(&Config::read()?);
+        let mut specials = HashMap::new();
+        self.urgent.borrow_mut().clear();
+        self.change_workspace()?;
+        self.change_window()?;
+        self.change_scratchpads(&specials)?;
+        self.change_submap("")?;
+
+        let active = self.get_active()?;
+        let mut class = active.as_ref().map_or(String::new(), |c| c.class.clone());
+        let mut current = active.map(|c| c.address.trim_start_matches("0x").to_string());
+        memory.focus(self, current.as_deref(), &class)?;
+        for line in reader.lines().map_while(Result::ok) {
+            match Event::from(line.as_str()) {
+                Event::Workspace { .. } => {
+                    self.change_workspace()?;
+                    if Config::read()?.compositor.colors.mode == ColorMode::Workspace {
+                        self.change_color(&class)?;
+                    }
+                }
+                Event::FocusedMonitor { .. }
+                | Event::CreateWorkspace { .. }
+                | Event::DestroyWorkspace { .. }
+                | Event::MoveWorkspace { .. }
+                | Event::MonitorAdded { .. }
+                | Event::MonitorRemoved { .. }
+                | Event::MoveWindow { .. }
+                | Event::WindowTitle { .. } => self.change_workspace()?,
+                Event::Fullscreen { .. } => {
+                    self.change_workspace()?;
+                    self.change_window()?;
+                }
+                Event::OpenWindow {
+                    address,
+                    class: opened,
+                    ..
+                } => {
+                    memory.open(&address, &opened);
+                    self.change_workspace()?;
+                    self.change_scratchpads(&specials)?;
+                }
+                Event::CloseWindow { address } => {
+                    memory.close(&address);
+                    self.change_workspace()?;
+                    self.change_scratchpads(&specials)?;
+                }
+                Event::Submap { name } => self.change_submap(&name)?,
+                Event::ActiveSpecial { workspace, monitor } => {
+                    specials.insert(monitor, workspace);
+                    self.change_scratchpads(&specials)?;
+                }
+                Event::Urgent { address } => {
+                    self.urgent.borrow_mut().insert(address);
+                    self.change_workspace()?;
+                }
+                Event::ActiveWindow { class: focused, .. } => {
+                    self.change_window()?;
+                    class = focused;
+                }
+                Event::ConfigReloaded => Logger::new("color").write(&self.get_color())?,
+                Event::ActiveLayout { layout, .. } => {
+                    memory.remember(self)?;
+                    self.update_layout(&layout)?;
+                }
+                Event::ActiveWindowV2 { address } => {
+                    if address
+                        .as_ref()
+                        .is_some_and(|address| self.urgent.borrow_mut().remove(address))
+                    {
+                        self.change_workspace()?;
+                    }
+                    if address != current {
+                        self.change_color(&class)?;
+                        memory.focus(self, address.as_deref(), &class)?;
+                        current = address;
+                    }
+                }
+                _ => (),
             }
         }
 
         Ok(())
     }
 
-    pub fn running() -> bool {
-        let pgrep = Command::new("pgrep").arg("Hyprland").output();
-        pgrep.is_ok_and(|output| !output.stdout.is_empty())
+    fn get_workspaces(&self) -> anyhow::Result<Vec<WorkspaceState>> {
+        let icons = Config::read()?.compositor.icons;
+        let urgent = self.urgent.borrow();
+        let monitors = self.query::<Vec<HyprMonitor>>("monitors")?;
+        let clients = self
+            .query::<Vec<HyprClient>>("clients")?
+            .into_iter()
+            .map(|client| {
+                let address = client.address.trim_start_matches("0x").to_string();
+                ClientState {
+                    urgent: urgent.contains(&address),
+                    address,
+                    workspace: client.workspace.id,
+                    icon: WindowState::icon(&client.class, &icons),
+                    class: client.class,
+                    title: client.title,
+                    pid: client.pid,
+                    fullscreen: client.fullscreen,
+                }
+            })
+            .collect::<Vec<ClientState>>();
+        Ok(self
+            .query::<Vec<HyprWorkspace>>("workspaces")?
+            .into_iter()
+            // special workspaces, e.g. scratchpads, aren't shown in the bar
+            .filter(|workspace| workspace.id >= 0)
+            .map(|workspace| {
+                let shown = monitors
+                    .iter()
+                    .find(|m| m.name == workspace.monitor && m.active_workspace.id == workspace.id);
+                WorkspaceState::new(
+                    workspace.id,
+                    &workspace.monitor,
+                    shown.is_some_and(|m| m.focused),
+                    shown.is_some(),
+                    clients.clone(),
+                )
+            })
+            .collect())
     }
 
-    pub fn change_layout(layout: Layout) -> anyhow::Result<()> {
-        Keyword::set("input:kb_layout", layout.to_string())?;
-        Logger::new("layout").write(&layout)
+    fn get_workspace(&self) -> anyhow::Result<i32> {
+        Ok(self.query::<HyprWorkspace>("activeworkspace")?.id)
     }
 
-    pub fn change_workspace() -> anyhow::Result<()> {
-        let mut states = Workspaces::get()?
-            .flat_map(WorkspaceState::try_from)
-            .collect::<Vec<WorkspaceState>>();
-        for id in 1..=5 {
-            if states.iter().all(|state| state.id != id) {
-                states.push(WorkspaceState::new(id, 0, false));
-            }
+    fn get_window(&self) -> anyhow::Result<WindowState> {
+        let icons = Config::read()?.compositor.icons;
+        let active = self.get_active()?;
+        Ok(WindowState::new(
+            active.as_ref().map_or("", |client| &client.class),
+            active.as_ref().map_or("", |client| &client.title),
+            active.as_ref().is_some_and(|client| client.fullscreen),
+            &icons,
+        ))
+    }
+
+    fn get_layout(&self) -> anyhow::Result<LayoutConfig> {
+        let keyboards = self.query::<HyprDevices>("devices")?.keyboards;
+        let keyboard = keyboards
+            .iter()
+            .find(|keyboard| keyboard.main)
+            .or(keyboards.first())
+            .ok_or(anyhow!("No keyboard found"))?;
+        // kb_layout and kb_variant list every layout, see set_layout
+        let pick = |list: &str| {
+            let item = list.split(',').nth(keyboard.active_layout_index);
+            item.unwrap_or("").to_string()
+        };
+        Ok(LayoutConfig {
+            layout: pick(&keyboard.layout),
+            variant: pick(&keyboard.variant),
+            options: keyboard.options.clone(),
+        })
+    }
+
+    // all layouts are loaded at once and switched between, as setting kb_layout
+    // and kb_variant one after the other reloads the keymap with a mismatched
+    // pair and sends an activelayout event for each step
+    fn set_layout(&self, layout: &LayoutConfig) -> anyhow::Result<()> {
+        let layouts = Config::read()?.compositor.layouts;
+        let index = layouts
+            .iter()
+            .position(|l| l.layout == layout.layout && l.variant == layout.variant)
+            .ok_or(anyhow!("Layout '{layout}' is not configured"))?;
+        self.switch_layout(&layouts, index)
+    }
+
+    fn get_color(&self) -> String {
+        let active = self
+            .get_option("general:col.active_border")
+            .ok()
+            .and_then(|option| Self::parse_color(&option));
+        active
+            .or_else(|| Logger::new("color").read().ok())
+            .or_else(|| {
+                let config = Config::read().ok()?;
+                config.compositor.colors.palette.first().cloned()
+            })
+            .unwrap_or("7aa2f7".to_string())
+    }
+
+    fn set_color(&self, colors: &[String], angle: u32) -> anyhow::Result<()> {
+        let mut border = colors
+            .iter()
+            .map(|color| format!("rgba({color}ee)"))
+            .collect::<Vec<String>>()
+            .join(" ");
+        if colors.len() > 1 {
+            border = format!("{border} {angle}deg");
         }
-        states.sort_by_key(|workspace| workspace.id);
-        Logger::new("workspaces").write(&states)
+        self.keyword("general:col.active_border", &border)
     }
+}
+
+// the parts of hyprctl's JSON replies that are used, which older and newer
+// releases agree on
+
+// value of an unset int in getoption replies of older releases
+const UNSET: i64 = -9223372036854775807;
+
+#[derive(Deserialize)]
+struct HyprOption {
+    int: Option<i64>,
+    str: Option<String>,
+    data: Option<String>,
+    custom: Option<String>,
+}
 
-    pub fn change_color() -> anyhow::Result<()> {
-        let color = Self::rand_color();
-        Keyword::set("general:col.active_border", format!("rgba({color}ee)"))?;
-        Logger::new("color").write(&color)
+#[derive(Deserialize)]
+struct HyprWorkspace {
+    id: i32,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    monitor: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HyprMonitor {
+    name: String,
+    focused: bool,
+    active_workspace: HyprWorkspace,
+}
+
+#[derive(Deserialize)]
+struct HyprClient {
+    address: String,
+    workspace: HyprWorkspace,
+    class: String,
+    title: String,
+    pid: i32,
+    #[serde(deserialize_with = "fullscreen")]
+    fullscreen: bool,
+}
+
+// a bool on older releases, the fullscreen mode on newer ones
+fn fullscreen<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Bool(fullscreen) => fullscreen,
+        serde_json::Value::Number(mode) => mode.as_u64() != Some(0),
+        _ => false,
+    })
+}
+
+#[derive(Deserialize)]
+struct HyprDevices {
+    keyboards: Vec<HyprKeyboard>,
+}
+
+#[derive(Deserialize)]
+struct HyprKeyboard {
+    layout: String,
+    variant: String,
+    options: String,
+    #[serde(default)]
+    main: bool,
+    #[serde(default)]
+    active_layout_index: usize,
+}
+
+#[derive(Deserialize)]
+struct HyprBind {
+    modmask: u32,
+    submap: String,
+    key: String,
+    dispatcher: String,
+    arg: String,
+}
+
+struct LayoutRecall {
+    mode: LayoutMemory,
+    default: Option<LayoutConfig>,
+    layouts: HashMap<String, LayoutConfig>,
+    focused: Option<String>,
+}
+
+impl LayoutRecall {
+    pub fn new(config: &Config) -> Self {
+        let default = config.hypr.default_layout.as_ref().and_then(|default| {
+            config
+                .compositor
+                .layouts
+                .iter()
+                .find(|l| l.to_string() == *default || l.layout == *default)
+                .cloned()
+        });
+        Self {
+            mode: config.hypr.layout_memory,
+            default,
+            layouts: HashMap::new(),
+            focused: None,
+        }
     }
 
-    pub fn get_color() -> String {
-        // temporary fix because hyprctl doesn't work for colors
-        match Logger::new("color").read() {
-            Ok(color) => color,
-            Err(_) => "7aa2f7".to_string(),
+    pub fn open(&mut self, address: &str, class: &str) {
+        if let (Some(key), Some(default)) = (self.key(address, class), &self.default) {
+            self.layouts.entry(key).or_insert(default.clone());
         }
     }
 
-    fn get_active_address() -> anyhow::Result<Option<Vec<u8>>> {
-        Ok(Client::get_active()?.map(|client| client.address.as_vec()))
+    pub fn close(&mut self, address: &str) {
+        if self.mode == LayoutMemory::Window {
+            self.layouts.remove(address);
+        }
     }
 
-    fn stream() -> anyhow::Result<UnixStream> {
-        let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")?;
-        Ok(UnixStream::connect(format!(
-            "/tmp/hypr/{signature}/.socket2.sock"
-        ))?)
+    pub fn focus(&mut self, hypr: &Hypr, address: Option<&str>, class: &str) -> anyhow::Result<()> {
+        // the window being left keeps the layout it had, even if it never changed
+        self.remember(hypr)?;
+        self.focused = address.and_then(|address| self.key(address, class));
+        let Some(key) = self.focused.clone() else {
+            return Ok(());
+        };
+        let layout = match (self.layouts.get(&key), &self.default) {
+            (Some(layout), _) | (None, Some(layout)) => layout.clone(),
+            // first seen, e.g. opened before the daemon started
+            (None, None) => return self.remember(hypr),
+        };
+        let current = hypr.get_layout()?;
+        if layout.layout != current.layout || layout.variant != current.variant {
+            hypr.set_layout(&layout)?;
+        }
+        self.layouts.insert(key, layout);
+        Ok(())
     }
 
-    fn rand_color() -> String {
-        (*COLORS
-            .iter()
-            .filter(|color| (**color).to_string() != Self::get_color())
-            .choose(&mut rand::thread_rng())
-            .unwrap())
-        .to_string()
+    pub fn remember(&mut self, hypr: &Hypr) -> anyhow::Result<()> {
+        if let Some(key) = &self.focused {
+            self.layouts.insert(key.clone(), hypr.get_layout()?);
+        }
+        Ok(())
+    }
+
+    fn key(&self, address: &str, class: &str) -> Option<String> {
+        match self.mode {
+            LayoutMemory::Off => None,
+            LayoutMemory::Window => Some(address.to_string()),
+            LayoutMemory::Class => Some(class.to_string()),
+        }
     }
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct WorkspaceState {
-    id: i32,
-    windows: u16,
+struct ScratchpadState {
+    name: String,
+    class: String,
+    running: bool,
+    visible: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SubmapState {
+    name: String,
     active: bool,
+    binds: Vec<BindState>,
 }
 
-impl TryFrom<Workspace> for WorkspaceState {
-    type Error = anyhow::Error;
-    fn try_from(value: Workspace) -> Result<Self, Self::Error> {
-        Ok(Self::new(
-            value.id,
-            value.windows,
-            value.id == Workspace::get_active()?.id,
-        ))
+impl SubmapState {
+    pub fn notify(&self) -> anyhow::Result<()> {
+        let body = self
+            .binds
+            .iter()
+            .map(|bind| format!("{}  {}", bind.keys, bind.action))
+            .collect::<Vec<String>>()
+            .join("\n");
+        Notifier::new("submap").send(&format!("Submap '{}'", self.name), &body, None, None)
     }
 }
 
-impl WorkspaceState {
-    pub fn new(id: i32, windows: u16, active: bool) -> Self {
+#[derive(Serialize, Deserialize)]
+struct BindState {
+    keys: String,
+    action: String,
+}
+
+impl BindState {
+    pub fn new(bind: &HyprBind) -> Self {
+        // see wlr_keyboard_modifier
+        let modifiers = [(64, "SUPER"), (4, "CTRL"), (8, "ALT"), (1, "SHIFT")];
+        let keys = modifiers
+            .iter()
+            .filter(|(mask, _)| bind.modmask & mask != 0)
+            .map(|(_, name)| name.to_string())
+            .chain([bind.key.clone()])
+            .collect::<Vec<String>>()
+            .join("+");
+        let action = format!("{} {}", bind.dispatcher, bind.arg);
         Self {
-            id,
-            windows,
-            active,
+            keys,
+            action: action.trim().to_string(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, ValueEnum)]
-pub enum Layout {
-    Fr,
-    Us,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::sync::{Arc, Mutex};
+
+    // a fake command socket answering from a table, every request is recorded
+    struct Server {
+        instance: PathBuf,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Server {
+        fn start(name: &str, replies: &[(&str, &str)]) -> Self {
+            let instance =
+                std::env::temp_dir().join(format!("emanager-hypr-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&instance);
+            std::fs::create_dir_all(&instance).unwrap();
+            let listener = UnixListener::bind(instance.join(".socket.sock")).unwrap();
+            let replies = replies
+                .iter()
+                .map(|(command, reply)| (command.to_string(), reply.to_string()))
+                .collect::<HashMap<String, String>>();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+            std::thread::spawn(move || {
+                for mut stream in listener.incoming().map_while(Result::ok) {
+                    let mut request = [0; 1024];
+                    let length = stream.read(&mut request).unwrap();
+                    let request = String::from_utf8_lossy(&request[..length]).to_string();
+                    let reply = replies.get(&request).cloned().unwrap_or("ok".to_string());
+                    recorded.lock().unwrap().push(request);
+                    stream.write_all(reply.as_bytes()).unwrap();
+                }
+            });
+            Self { instance, requests }
+        }
+
+        fn hypr(&self) -> Hypr {
+            Hypr {
+                instance: Some(self.instance.clone()),
+                urgent: RefCell::new(HashSet::new()),
+            }
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.instance);
+        }
+    }
 
-impl Display for Layout {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Layout::Fr => write!(f, "fr"),
-            Layout::Us => write!(f, "us"),
+    fn layouts() -> Vec<LayoutConfig> {
+        let mut intl = LayoutConfig::new("us");
+        intl.variant = "intl".to_string();
+        vec![LayoutConfig::new("fr"), intl]
+    }
+
+    #[test]
+    fn switch_layout_loads_layouts_once() {
+        let server = Server::start(
+            "load",
+            &[
+                ("j/getoption input:kb_layout", r#"{"str": "fr"}"#),
+                ("j/getoption input:kb_variant", r#"{"str": ""}"#),
+                ("j/getoption input:kb_options", r#"{"str": ""}"#),
+            ],
+        );
+        server.hypr().switch_layout(&layouts(), 1).unwrap();
+        assert_eq!(
+            server.requests(),
+            [
+                "j/getoption input:kb_layout",
+                "keyword input:kb_variant ",
+                "keyword input:kb_layout fr,us",
+                "keyword input:kb_variant ,intl",
+                "j/getoption input:kb_options",
+                "switchxkblayout all 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn switch_layout_only_switches() {
+        let server = Server::start(
+            "switch",
+            &[
+                ("j/getoption input:kb_layout", r#"{"str": "fr,us"}"#),
+                ("j/getoption input:kb_variant", r#"{"str": ",intl"}"#),
+                ("j/getoption input:kb_options", r#"{"str": ""}"#),
+            ],
+        );
+        server.hypr().switch_layout(&layouts(), 0).unwrap();
+        assert_eq!(
+            server.requests(),
+            [
+                "j/getoption input:kb_layout",
+                "j/getoption input:kb_variant",
+                "j/getoption input:kb_options",
+                "switchxkblayout all 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn switch_layout_reports_errors() {
+        let server = Server::start(
+            "error",
+            &[
+                ("j/getoption input:kb_layout", r#"{"str": "fr"}"#),
+                ("j/getoption input:kb_variant", r#"{"str": ""}"#),
+                ("j/getoption input:kb_options", r#"{"str": ""}"#),
+                ("switchxkblayout all 0", "Invalid dispatcher"),
+            ],
+        );
+        let mut layouts = layouts();
+        layouts.truncate(1);
+        let error = server.hypr().switch_layout(&layouts, 0).unwrap_err();
+        assert_eq!(error.to_string(), "Hyprland: Invalid dispatcher");
+    }
+
+    fn client(address: &str, workspace: (i32, &str), class: &str) -> String {
+        format!(
+            r#"{{"address": "{address}", "workspace": {{"id": {}, "name": "{}"}},
+                "class": "{class}", "title": "", "pid": 1, "fullscreen": 0}}"#,
+            workspace.0, workspace.1
+        )
+    }
+
+    fn scratchpad() -> ScratchpadConfig {
+        ScratchpadConfig {
+            class: "kitty".to_string(),
+            command: "kitty".to_string(),
         }
     }
+
+    #[test]
+    fn scratchpad_keeps_its_own_window() {
+        let clients = format!(
+            "[{}, {}]",
+            client("0x1", (1, "1"), "kitty"),
+            client("0x2", (-98, "special:term"), "kitty")
+        );
+        let server = Server::start("scratch", &[("j/clients", &clients)]);
+        server
+            .hypr()
+            .toggle_scratchpad("term", &scratchpad())
+            .unwrap();
+        assert_eq!(
+            server.requests(),
+            ["j/clients", "dispatch togglespecialworkspace term"]
+        );
+    }
+
+    #[test]
+    fn scratchpad_takes_or_starts_a_window() {
+        let clients = format!("[{}]", client("0x1", (1, "1"), "kitty"));
+        let server = Server::start("take", &[("j/clients", &clients)]);
+        server
+            .hypr()
+            .toggle_scratchpad("term", &scratchpad())
+            .unwrap();
+        assert_eq!(
+            server.requests(),
+            [
+                "j/clients",
+                "dispatch movetoworkspacesilent special:term,address:0x1",
+                "dispatch togglespecialworkspace term",
+            ]
+        );
+
+        let server = Server::start("start", &[("j/clients", "[]")]);
+        server
+            .hypr()
+            .toggle_scratchpad("term", &scratchpad())
+            .unwrap();
+        assert_eq!(
+            server.requests(),
+            [
+                "j/clients",
+                "dispatch exec [workspace special:term silent] kitty",
+                "dispatch togglespecialworkspace term",
+            ]
+        );
+    }
+
+    #[test]
+    fn special_workspaces_are_hidden() {
+        let clients = format!("[{}]", client("0x2", (-98, "special:term"), "kitty"));
+        let server = Server::start(
+            "workspaces",
+            &[
+                (
+                    "j/monitors",
+                    r#"[{"name": "eDP-1", "focused": true, "activeWorkspace": {"id": 1}}]"#,
+                ),
+                ("j/clients", &clients),
+                (
+                    "j/workspaces",
+                    r#"[{"id": 1, "name": "1", "monitor": "eDP-1"},
+                        {"id": -98, "name": "special:term", "monitor": "eDP-1"}]"#,
+                ),
+            ],
+        );
+        let workspaces = server.hypr().get_workspaces().unwrap();
+        let ids = workspaces.iter().map(|w| w.id).collect::<Vec<i32>>();
+        assert_eq!(ids, [1]);
+        assert!(workspaces[0].active && workspaces[0].clients.is_empty());
+    }
 }
@@ -0,0 +1,271 @@
+use crate::config::Config;
+use crate::logger::Logger;
+use crate::notifier::Notifier;
+use crate::system::System;
+use crate::utils::{format_duration, parse_duration, timestamp};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zbus::blocking::{Connection, MessageIterator, Proxy, ProxyBuilder};
+use zbus::{CacheProperties, MatchRule, Message, MessageType};
+
+const DBUS: &str = "org.freedesktop.DBus";
+const MPRIS: &str = "org.mpris.MediaPlayer2";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const MPRIS_PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+
+// shared by the compositor and media listeners, one logind inhibitor for all reasons
+static INHIBITOR: Mutex<Inhibitor> = Mutex::new(Inhibitor {
+    manual: None,
+    window: None,
+    players: Vec::new(),
+    handle: None,
+});
+
+struct Inhibitor {
+    manual: Option<Manual>,
+    window: Option<String>,
+    players: Vec<String>,
+    handle: Option<Arc<Message>>,
+}
+
+// inhibitor asked for with 'emanager system inhibit on'
+#[derive(Clone)]
+struct Manual {
+    reason: String,
+    until: Option<u64>,
+}
+
+pub struct Inhibit;
+
+impl Inhibit {
+    pub fn listen() -> anyhow::Result<()> {
+        Self::apply(&mut INHIBITOR.lock().unwrap())?;
+        if !Config::read()?.inhibit.media {
+            return Ok(());
+        }
+        let bus = Connection::session()?;
+        let players = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .path(MPRIS_PATH)?
+            .build();
+        // players quitting don't always report being stopped
+        let owners = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .member("NameOwnerChanged")?
+            .arg0ns(MPRIS)?
+            .build();
+        std::thread::scope(|scope| {
+            let handle = scope.spawn(|| Self::listen_rule(players, &bus));
+            Self::listen_rule(owners, &bus)?;
+            handle.join().unwrap()
+        })
+    }
+
+    // runs inside the daemon, which owns the inhibitor
+    pub fn handle(operation: InhibitOp) -> anyhow::Result<String> {
+        let mut inhibitor = INHIBITOR.lock().unwrap();
+        let operation = match operation {
+            InhibitOp::Toggle if inhibitor.manual.is_some() => InhibitOp::Off,
+            InhibitOp::Toggle => InhibitOp::On {
+                duration: None,
+                reason: None,
+            },
+            operation => operation,
+        };
+        let notifier = Notifier::new("inhibit");
+        match operation {
+            InhibitOp::On { duration, reason } => {
+                let until = duration.map(|duration| timestamp() + duration.as_secs());
+                inhibitor.manual = Some(Manual {
+                    reason: reason.unwrap_or("Inhibited manually".to_string()),
+                    until,
+                });
+                if let Some(until) = until {
+                    std::thread::spawn(move || {
+                        if let Err(e) = Self::expire(until) {
+                            eprintln!("{e}");
+                        }
+                    });
+                }
+                Self::apply(&mut inhibitor)?;
+                let body = match duration {
+                    Some(duration) => format!("Idle inhibited for {}", format_duration(duration)),
+                    None => "Idle inhibited".to_string(),
+                };
+                notifier.send("Idle inhibitor", &body, None, None)?;
+                Ok(String::new())
+            }
+            InhibitOp::Off => {
+                if inhibitor.manual.take().is_some() {
+                    Self::apply(&mut inhibitor)?;
+                    notifier.send("Idle inhibitor", "Released", None, None)?;
+                }
+                Ok(String::new())
+            }
+            InhibitOp::Status | InhibitOp::Toggle => {
+                Ok(serde_json::to_string(&Self::get_state(&inhibitor))?)
+            }
+        }
+    }
+
+    pub fn focus(class: &str, fullscreen: bool) -> anyhow::Result<()> {
+        let config = Config::read()?.inhibit;
+        let window = if config.deny.iter().any(|deny| deny == class) {
+            None
+        } else if config.allow.iter().any(|allow| allow == class) {
+            Some(format!("'{class}' is focused"))
+        } else if config.fullscreen && fullscreen {
+            Some(format!("'{class}' is fullscreen"))
+        } else {
+            None
+        };
+        let mut inhibitor = INHIBITOR.lock().unwrap();
+        if inhibitor.window != window {
+            inhibitor.window = window;
+            Self::apply(&mut inhibitor)?;
+        }
+        Ok(())
+    }
+
+    fn listen_rule(rule: MatchRule, bus: &Connection) -> anyhow::Result<()> {
+        let signals = MessageIterator::for_match_rule(rule, bus, None)?;
+        Self::play(Self::get_players(bus)?)?;
+        for signal in signals {
+            signal?;
+            Self::play(Self::get_players(bus)?)?;
+        }
+        Ok(())
+    }
+
+    // ticks on the wall clock so that suspending doesn't delay the expiry
+    fn expire(until: u64) -> anyhow::Result<()> {
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let mut inhibitor = INHIBITOR.lock().unwrap();
+            // a later 'inhibit on' or 'off' replaced this one
+            if inhibitor.manual.as_ref().and_then(|manual| manual.until) != Some(until) {
+                return Ok(());
+            }
+            if timestamp() >= until {
+                inhibitor.manual = None;
+                Self::apply(&mut inhibitor)?;
+                return Notifier::new("inhibit").send("Idle inhibitor", "Expired", None, None);
+            }
+        }
+    }
+
+    fn play(players: Vec<String>) -> anyhow::Result<()> {
+        let mut inhibitor = INHIBITOR.lock().unwrap();
+        if inhibitor.players != players {
+            inhibitor.players = players;
+            Self::apply(&mut inhibitor)?;
+        }
+        Ok(())
+    }
+
+    fn apply(inhibitor: &mut Inhibitor) -> anyhow::Result<()> {
+        let state = Self::get_state(inhibitor);
+        match (state.active, &inhibitor.handle) {
+            (true, None) => inhibitor.handle = Some(System::inhibitor(&state.reason)?),
+            (false, Some(_)) => inhibitor.handle = None,
+            _ => (),
+        }
+        state.log()
+    }
+
+    fn get_state(inhibitor: &Inhibitor) -> InhibitState {
+        let manual = inhibitor.manual.clone();
+        let reasons = manual
+            .iter()
+            .map(|manual| manual.reason.clone())
+            .chain(inhibitor.window.iter().cloned())
+            .chain(
+                inhibitor
+                    .players
+                    .iter()
+                    .map(|player| format!("'{player}' is playing")),
+            )
+            .collect::<Vec<String>>();
+        InhibitState {
+            active: !reasons.is_empty(),
+            reason: reasons.join(", "),
+            manual: manual.is_some(),
+            until: manual.and_then(|manual| manual.until),
+        }
+    }
+
+    fn get_players(bus: &Connection) -> anyhow::Result<Vec<String>> {
+        let deny = Config::read()?.inhibit.deny;
+        let dbus = Self::proxy(bus, DBUS, "/org/freedesktop/DBus", DBUS)?;
+        let names: Vec<String> = dbus.call("ListNames", &())?;
+        let mut players = Vec::new();
+        for name in names.iter().filter(|name| name.starts_with(MPRIS)) {
+            // e.g. org.mpris.MediaPlayer2.firefox.instance1234
+            let player = name[MPRIS.len()..]
+                .trim_start_matches('.')
+                .split('.')
+                .next()
+                .unwrap_or("")
+                .to_string();
+            let proxy = Self::proxy(bus, name, MPRIS_PATH, MPRIS_PLAYER)?;
+            let status = proxy.get_property::<String>("PlaybackStatus");
+            if status.is_ok_and(|status| status == "Playing")
+                && !deny.contains(&player)
+                && !players.contains(&player)
+            {
+                players.push(player);
+            }
+        }
+        players.sort();
+        Ok(players)
+    }
+
+    fn proxy<'a>(
+        bus: &Connection,
+        destination: &'a str,
+        path: &'a str,
+        interface: &'a str,
+    ) -> anyhow::Result<Proxy<'a>> {
+        Ok(ProxyBuilder::new_bare(bus)
+            .destination(destination)?
+            .path(path)?
+            .interface(interface)?
+            .cache_properties(CacheProperties::No)
+            .build()?)
+    }
+}
+
+#[derive(Clone, Subcommand)]
+pub enum InhibitOp {
+    /// Inhibit idle until turned off or for a while
+    On {
+        /// Release after a duration, e.g. '90m'
+        #[arg(long = "for", value_parser = parse_duration)]
+        duration: Option<Duration>,
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Release idle
+    Off,
+    /// Turn the manual inhibitor on or off
+    Toggle,
+    /// Print inhibitor state
+    Status,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InhibitState {
+    active: bool,
+    reason: String,
+    manual: bool,
+    // unix timestamp at which the manual inhibitor expires
+    until: Option<u64>,
+}
+
+impl InhibitState {
+    pub fn log(&self) -> anyhow::Result<()> {
+        Logger::new("inhibit").write(self)
+    }
+}
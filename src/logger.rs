@@ -2,13 +2,25 @@ use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 use std::{io::Write, marker::PhantomData};
 
-const DIR: &str = "/home/emanuel/.local/state/emanager";
+pub const DIR: &str = "/home/emanuel/.local/state/emanager";
 
 pub struct Logger<T: Serialize + for<'a> Deserialize<'a>> {
     file: String,
     phantom: PhantomData<T>,
 }
 
+// shared with History, which keeps every line instead of the last one
+pub fn append<T: Serialize>(file: &str, value: &T) -> anyhow::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)?;
+    let json = serde_json::to_vec(value)?;
+    file.write_all(&json)?;
+    file.write_all(b"\n")?;
+    Ok(())
+}
+
 impl<T: Serialize + for<'a> Deserialize<'a>> Logger<T> {
     pub fn new(name: &str) -> Self {
         Self {
@@ -20,14 +32,7 @@ impl<T: Serialize + for<'a> Deserialize<'a>> Logger<T> {
     pub fn write(&self, state: &T) -> anyhow::Result<()> {
         std::fs::create_dir_all(DIR)?;
         self.truncate()?;
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.file)?;
-        let json = serde_json::to_vec(&state)?;
-        file.write_all(&json)?;
-        file.write_all(b"\n")?;
-        Ok(())
+        append(&self.file, state)
     }
 
     pub fn read(&self) -> anyhow::Result<T> {
@@ -42,6 +47,7 @@ impl<T: Serialize + for<'a> Deserialize<'a>> Logger<T> {
     fn truncate(&self) -> anyhow::Result<()> {
         if let Ok(file) = std::fs::OpenOptions::new()
             .create(true)
+            .truncate(false)
             .write(true)
             .open(&self.file)
         {
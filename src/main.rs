@@ -1,13 +1,25 @@
 use clap::Parser;
 
 mod acpi;
+mod airplane;
 mod args;
 mod battery;
+mod bluetooth;
 mod brightness;
+mod compositor;
+mod config;
+mod event;
+mod history;
 mod hypr;
+mod inhibit;
 mod logger;
 mod manager;
+mod network;
 mod notifier;
+mod peripherals;
+mod power;
+mod schedule;
+mod sway;
 mod system;
 mod utils;
 mod volume;
@@ -1,12 +1,27 @@
 use crate::acpi::Acpi;
-use crate::args::Command;
+use crate::airplane::Airplane;
+use crate::args::{Args, Command};
 use crate::battery::Battery;
+use crate::bluetooth::Bluetooth;
 use crate::brightness::Brightness;
+use crate::compositor;
 use crate::hypr::Hypr;
-use crate::system::System;
+use crate::inhibit::Inhibit;
+use crate::network::Network;
+use crate::peripherals::Peripherals;
+use crate::power::{Power, PowerOp};
+use crate::schedule::Schedule;
+use crate::system::{System, SystemOp};
 use crate::volume::Volume;
 use crate::wifi::Wifi;
 use anyhow::anyhow;
+use clap::Parser;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct Manager;
 
@@ -16,10 +31,16 @@ impl Manager {
             return Err(anyhow!("Manager is already running"));
         }
         std::thread::scope(|scope| -> anyhow::Result<()> {
-            let handle = scope.spawn(|| Acpi::listen());
-            scope.spawn(|| Battery::listen());
-            scope.spawn(|| Hypr::listen());
-            scope.spawn(|| Wifi::listen());
+            let handle = scope.spawn(Acpi::listen);
+            scope.spawn(Battery::listen);
+            scope.spawn(compositor::listen);
+            scope.spawn(Power::listen);
+            scope.spawn(Network::listen);
+            scope.spawn(Airplane::listen);
+            scope.spawn(Bluetooth::listen);
+            scope.spawn(Peripherals::listen);
+            scope.spawn(Inhibit::listen);
+            scope.spawn(Self::serve);
 
             handle.join().unwrap()
         })
@@ -30,11 +51,82 @@ impl Manager {
             Command::System { operation } => System::handle(operation),
             Command::Brightness { operation } => Brightness::handle(operation),
             Command::Volume { operation } => Volume::handle(operation),
-            Command::Layout { layout } => Hypr::change_layout(layout),
+            Command::Wifi { operation } => Wifi::handle(operation),
+            Command::Bluetooth { operation } => Bluetooth::handle(operation),
+            Command::Airplane { operation } => Airplane::handle(operation),
+            Command::Battery { operation } => Battery::handle(operation),
+            Command::Power { operation } => Power::handle(operation),
+            Command::Layout { operation } => compositor::current()?.change_layout(operation),
+            Command::Scratch { name } => Hypr::new().scratch(&name),
             _ => Ok(()),
         }
     }
 
+    // forwards the current command line to the daemon and prints its output
+    pub fn request() -> anyhow::Result<()> {
+        let mut stream =
+            UnixStream::connect(Self::socket()).map_err(|_| anyhow!("Manager is not running"))?;
+        let args = std::env::args().collect::<Vec<String>>();
+        writeln!(stream, "{}", serde_json::to_string(&args)?)?;
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        match serde_json::from_str::<Result<String, String>>(&reply)? {
+            Ok(output) if output.is_empty() => Ok(()),
+            Ok(output) => {
+                println!("{output}");
+                Ok(())
+            }
+            Err(e) => Err(anyhow!(e)),
+        }
+    }
+
+    fn serve() -> anyhow::Result<()> {
+        Schedule::restore()?;
+        let socket = Self::socket();
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)?;
+        for stream in listener.incoming() {
+            // a client going away must not stop the others from being served
+            let served = stream.map_err(anyhow::Error::from).and_then(Self::respond);
+            if let Err(e) = served {
+                eprintln!("Failed to answer a request ({e})");
+            }
+        }
+        Ok(())
+    }
+
+    fn respond(mut stream: UnixStream) -> anyhow::Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+        let reply = serde_json::from_str::<Vec<String>>(&request)
+            .map_err(anyhow::Error::from)
+            .and_then(Self::handle_request)
+            .map_err(|e| e.to_string());
+        writeln!(stream, "{}", serde_json::to_string(&reply)?)?;
+        Ok(())
+    }
+
+    fn handle_request(args: Vec<String>) -> anyhow::Result<String> {
+        match Args::try_parse_from(args)?.command {
+            Command::System { operation } => match (operation.action(), operation) {
+                (Some((action, when)), _) => Schedule::add(action, &when),
+                (None, SystemOp::Inhibit { operation }) => Inhibit::handle(operation),
+                (None, SystemOp::Schedule { operation }) => Schedule::handle(operation),
+                _ => Err(anyhow!("Command is not handled by the daemon")),
+            },
+            Command::Power {
+                operation: PowerOp::Update,
+            } => Power::update(500, false).map(|_| String::new()),
+            _ => Err(anyhow!("Command is not handled by the daemon")),
+        }
+    }
+
+    fn socket() -> PathBuf {
+        let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or("/tmp".to_string());
+        Path::new(&runtime).join("emanager.sock")
+    }
+
     pub fn running() -> bool {
         let pgrep = std::process::Command::new("pgrep")
             .args(["-f", "emanager daemon"])
@@ -0,0 +1,390 @@
+use crate::logger::Logger;
+use crate::notifier::Notifier;
+use crate::system::System;
+use crate::wifi::{Wifi, WifiState};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::{Connection, MessageIterator, Proxy, ProxyBuilder};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+use zbus::{CacheProperties, MatchRule, MessageType};
+
+const NM: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const NM_DEVICE: &str = "org.freedesktop.NetworkManager.Device";
+const NM_ACTIVE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+const NM_IP4: &str = "org.freedesktop.NetworkManager.IP4Config";
+const NM_IP6: &str = "org.freedesktop.NetworkManager.IP6Config";
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+
+pub struct Network;
+
+impl Network {
+    pub fn listen() -> anyhow::Result<()> {
+        let mut current = None;
+        let mut failing = false;
+        loop {
+            let start = Instant::now();
+            if let Err(e) = Self::listen_nm(&mut current) {
+                // a listener that ran for a while was working, report its failure again
+                if !failing || start.elapsed() > Duration::from_secs(2) {
+                    eprintln!("NetworkManager D-Bus failed ({e}), polling nmcli meanwhile");
+                }
+                failing = true;
+            }
+            if let Ok(state) = Self::get_state_nmcli() {
+                Self::update(state, &mut current)?;
+            }
+            std::thread::sleep(Duration::from_secs(2));
+        }
+    }
+
+    pub fn proxy<'a>(
+        bus: &Connection,
+        path: &'a str,
+        interface: &'a str,
+    ) -> anyhow::Result<Proxy<'a>> {
+        Ok(ProxyBuilder::new_bare(bus)
+            .destination(NM)?
+            .path(path)?
+            .interface(interface)?
+            .cache_properties(CacheProperties::No)
+            .build()?)
+    }
+
+    fn listen_nm(current: &mut Option<NetworkState>) -> anyhow::Result<()> {
+        let bus = System::bus()?;
+        let dbus = DBusProxy::new(bus)?;
+        let mut rules = Vec::new();
+        let result = Self::watch_nm(bus, &dbus, &mut rules, current);
+        for rule in rules {
+            let _ = dbus.remove_match_rule(rule);
+        }
+        result
+    }
+
+    fn watch_nm(
+        bus: &Connection,
+        dbus: &DBusProxy,
+        rules: &mut Vec<MatchRule<'static>>,
+        current: &mut Option<NetworkState>,
+    ) -> anyhow::Result<()> {
+        let mut signals = MessageIterator::from(bus);
+        loop {
+            let (state, paths) = Self::get_state_nm(bus)?;
+            Self::update(state, current)?;
+            let wanted = Self::rules(&paths)?;
+            if wanted != *rules {
+                for rule in wanted.iter().filter(|rule| !rules.contains(rule)) {
+                    dbus.add_match_rule(rule.clone())?;
+                }
+                for rule in rules.iter().filter(|rule| !wanted.contains(rule)) {
+                    dbus.remove_match_rule(rule.clone())?;
+                }
+                *rules = wanted;
+                // the state may have changed before the new rules were in place
+                continue;
+            }
+            // the bus is shared, so other listeners' signals come through too
+            loop {
+                let signal = signals.next().ok_or(anyhow!("D-Bus connection closed"))??;
+                if rules
+                    .iter()
+                    .any(|rule| rule.matches(&signal).is_ok_and(|m| m))
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    // StateChanged on the manager, and PropertiesChanged on the manager and on
+    // the given objects, rather than every access point NetworkManager scans
+    fn rules(paths: &[String]) -> anyhow::Result<Vec<MatchRule<'static>>> {
+        let state = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .path(NM_PATH)?
+            .interface(NM)?
+            .member("StateChanged")?
+            .build();
+        let mut rules = vec![state];
+        for path in [NM_PATH.to_string()].iter().chain(paths) {
+            let properties = MatchRule::builder()
+                .msg_type(MessageType::Signal)
+                .path(path.clone())?
+                .interface(PROPERTIES)?
+                .member("PropertiesChanged")?
+                .build();
+            rules.push(properties);
+        }
+        Ok(rules)
+    }
+
+    fn update(state: NetworkState, current: &mut Option<NetworkState>) -> anyhow::Result<()> {
+        if Some(&state) != current.as_ref() {
+            let prev = current.as_ref().and_then(|s| s.associated.as_ref());
+            if state.associated.as_ref() != prev {
+                Wifi::record(state.associated.as_ref(), prev)?;
+            }
+            state.notify(current.take())?;
+            state.log()?;
+            *current = Some(state);
+        }
+        Ok(())
+    }
+
+    // the state and the objects whose changes affect it
+    fn get_state_nm(bus: &Connection) -> anyhow::Result<(NetworkState, Vec<String>)> {
+        let nm = Self::proxy(bus, NM_PATH, NM)?;
+        let connectivity = match nm.get_property::<u32>("Connectivity")? {
+            1 => "none",
+            2 => "portal",
+            3 => "limited",
+            4 => "full",
+            _ => "unknown",
+        };
+        let primary: OwnedObjectPath = nm.get_property("PrimaryConnection")?;
+        let actives: Vec<OwnedObjectPath> = nm.get_property("ActiveConnections")?;
+
+        let mut vpns = Vec::new();
+        for active in &actives {
+            let proxy = Self::proxy(bus, active.as_str(), NM_ACTIVE)?;
+            if Self::kind(&proxy.get_property::<String>("Type")?) == "vpn" {
+                vpns.push(proxy.get_property::<String>("Id")?);
+            }
+        }
+
+        let mut paths = Vec::new();
+        let associated = Self::get_wifi_nm(bus, &nm, &mut paths)?;
+        if primary.as_str() == "/" {
+            let mut state = NetworkState::new("none", "", Vec::new(), vpns, connectivity, None);
+            state.associated = associated;
+            return Ok((state, paths));
+        }
+        paths.push(primary.to_string());
+        let proxy = Self::proxy(bus, primary.as_str(), NM_ACTIVE)?;
+        let kind = Self::kind(&proxy.get_property::<String>("Type")?);
+        let devices: Vec<OwnedObjectPath> = proxy.get_property("Devices")?;
+        let interface = match devices.first() {
+            Some(device) => {
+                Self::proxy(bus, device.as_str(), NM_DEVICE)?.get_property::<String>("Interface")?
+            }
+            None => String::new(),
+        };
+        let wifi = associated.clone().filter(|_| kind == "wifi");
+        let mut addresses = Vec::new();
+        for (property, interface) in [("Ip4Config", NM_IP4), ("Ip6Config", NM_IP6)] {
+            let config: OwnedObjectPath = proxy.get_property(property)?;
+            if config.as_str() != "/" {
+                let data: Vec<HashMap<String, OwnedValue>> =
+                    Self::proxy(bus, config.as_str(), interface)?.get_property("AddressData")?;
+                addresses.extend(
+                    data.into_iter()
+                        .flat_map(|address| address.get("address").cloned())
+                        .flat_map(String::try_from),
+                );
+            }
+        }
+        let mut state = NetworkState::new(kind, &interface, addresses, vpns, connectivity, wifi);
+        state.associated = associated;
+        Ok((state, paths))
+    }
+
+    // the access point of the first associated Wi-Fi device, whatever the primary
+    // connection, watching the devices and that access point for changes
+    fn get_wifi_nm(
+        bus: &Connection,
+        nm: &Proxy,
+        paths: &mut Vec<String>,
+    ) -> anyhow::Result<Option<WifiState>> {
+        let devices: Vec<OwnedObjectPath> = nm.get_property("Devices")?;
+        for device in devices {
+            // see NMDeviceType
+            let kind: u32 =
+                Self::proxy(bus, device.as_str(), NM_DEVICE)?.get_property("DeviceType")?;
+            if kind != 2 {
+                continue;
+            }
+            paths.push(device.to_string());
+            if let Some(point) = Wifi::get_point(bus, device.as_str())? {
+                paths.push(point.to_string());
+                return Wifi::get_state_nm(bus, device.as_str());
+            }
+        }
+        Ok(None)
+    }
+
+    fn get_state_nmcli() -> anyhow::Result<NetworkState> {
+        let connectivity = Wifi::run(&["networking", "connectivity"])?;
+        let output = Wifi::run(&[
+            "-t",
+            "-f",
+            "type,device,name",
+            "connection",
+            "show",
+            "--active",
+        ])?;
+        let actives = output
+            .lines()
+            .map(Wifi::split)
+            .filter(|info| info.len() == 3)
+            .collect::<Vec<Vec<String>>>();
+        let vpns = actives
+            .iter()
+            .filter(|info| Self::kind(&info[0]) == "vpn")
+            .map(|info| info[2].clone())
+            .collect();
+        // bridges and other virtual links are only primary when nothing else is up
+        let primary = actives
+            .iter()
+            .filter(|info| !matches!(Self::kind(&info[0]), "vpn" | "none"))
+            .min_by_key(|info| Self::kind(&info[0]) == "other");
+        // fails without a Wi-Fi device
+        let associated = Wifi::get_state_nmcli().ok().flatten();
+        let mut state = match primary {
+            Some(info) => {
+                let kind = Self::kind(&info[0]);
+                let output = Wifi::run(&[
+                    "-t",
+                    "-f",
+                    "ip4.address,ip6.address",
+                    "dev",
+                    "show",
+                    &info[1],
+                ])?;
+                let addresses = output
+                    .lines()
+                    .map(Wifi::split)
+                    .flat_map(|info| info.get(1).cloned())
+                    .map(|address| address.split('/').next().unwrap_or("").to_string())
+                    .collect();
+                let wifi = associated.clone().filter(|_| kind == "wifi");
+                NetworkState::new(kind, &info[1], addresses, vpns, connectivity.trim(), wifi)
+            }
+            None => NetworkState::new("none", "", Vec::new(), vpns, connectivity.trim(), None),
+        };
+        state.associated = associated;
+        Ok(state)
+    }
+
+    fn kind(connection: &str) -> &'static str {
+        match connection {
+            "802-11-wireless" => "wifi",
+            "802-3-ethernet" => "ethernet",
+            "gsm" | "cdma" => "mobile",
+            "vpn" | "wireguard" | "tun" => "vpn",
+            "loopback" => "none",
+            _ => "other",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct NetworkState {
+    active: bool,
+    kind: String,
+    interface: String,
+    addresses: Vec<String>,
+    vpns: Vec<String>,
+    connectivity: String,
+    wifi: Option<WifiState>,
+    icon: String,
+    // Wi-Fi can stay associated while another connection is primary
+    #[serde(skip)]
+    associated: Option<WifiState>,
+}
+
+impl NetworkState {
+    pub fn new(
+        kind: &str,
+        interface: &str,
+        addresses: Vec<String>,
+        vpns: Vec<String>,
+        connectivity: &str,
+        wifi: Option<WifiState>,
+    ) -> Self {
+        let active = kind != "none";
+        let icon = if !active {
+            "󰤭 "
+        } else if connectivity == "portal" || connectivity == "limited" {
+            match kind {
+                "ethernet" => "󰈂 ",
+                _ => "󰤫 ",
+            }
+        } else {
+            match (kind, &wifi) {
+                ("wifi", Some(wifi)) => &wifi.icon,
+                ("wifi", None) => "󰤟 ",
+                ("ethernet", _) => "󰈀 ",
+                ("mobile", _) => "󰒢 ",
+                ("other", _) => "󰌗 ",
+                _ => "󰖂 ",
+            }
+        }
+        .to_string();
+        Self {
+            active,
+            kind: kind.to_string(),
+            interface: interface.to_string(),
+            addresses,
+            vpns,
+            connectivity: connectivity.to_string(),
+            wifi,
+            icon,
+            associated: None,
+        }
+    }
+
+    pub fn notify(&self, prev: Option<Self>) -> anyhow::Result<()> {
+        let notifier = Notifier::new("network");
+        let was_active = prev.as_ref().is_some_and(|s| s.active);
+        if self.active && (!was_active || prev.as_ref().is_some_and(|s| s.name() != self.name())) {
+            notifier.send(
+                "Network",
+                &format!("Connected to {}", self.name()),
+                None,
+                None,
+            )?;
+        } else if !self.active && was_active {
+            notifier.send(
+                "Network",
+                &format!("Disconnected from {}", prev.as_ref().unwrap().name()),
+                None,
+                None,
+            )?;
+        }
+        // own ids, so that one change doesn't hide another
+        let vpn_notifier = Notifier::new("network.vpn");
+        if let Some(prev) = prev.as_ref() {
+            for vpn in self.vpns.iter().filter(|vpn| !prev.vpns.contains(vpn)) {
+                vpn_notifier.send("VPN", &format!("Connected to '{vpn}'"), None, None)?;
+            }
+            for vpn in prev.vpns.iter().filter(|vpn| !self.vpns.contains(vpn)) {
+                vpn_notifier.send("VPN", &format!("Disconnected from '{vpn}'"), None, None)?;
+            }
+        }
+        if self.active && self.connectivity != prev.as_ref().map_or("", |s| &s.connectivity) {
+            let notifier = Notifier::new("network.connectivity");
+            match self.connectivity.as_str() {
+                "portal" => notifier.send("Network", "Captive portal detected", None, None)?,
+                "limited" => notifier.send("Network", "Limited connectivity", None, None)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn log(&self) -> anyhow::Result<()> {
+        Wifi::log(self.associated.as_ref())?;
+        Logger::new("network").write(self)
+    }
+
+    fn name(&self) -> String {
+        match &self.wifi {
+            Some(wifi) => format!("'{}'", wifi.ssid),
+            None => format!("{} '{}'", self.kind, self.interface),
+        }
+    }
+}
@@ -1,5 +1,9 @@
-use crate::{hypr::Hypr, logger::Logger};
-use notify_rust::{Hint, Notification, Urgency};
+use crate::{compositor, logger::Logger};
+use notify_rust::{Hint, Notification, NotificationHandle, Urgency};
+use zbus::blocking::Connection;
+
+const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
 
 pub struct Notifier {
     logger: Logger<u32>,
@@ -19,26 +23,63 @@ impl Notifier {
         urgency: Option<Urgency>,
         value: Option<u32>,
     ) -> anyhow::Result<()> {
-        if Hypr::running() {
-            let color = format!("#{}ee", Hypr::get_color());
-            let mut notif = Notification::new()
-                .summary(summary)
-                .body(body)
-                .hint(Hint::Urgency(urgency.unwrap_or(Urgency::Normal)))
-                .hint(Hint::Custom("frcolor".to_string(), color))
-                .finalize();
+        if let Some(mut notif) = Self::build(summary, body, urgency) {
             if let Some(value) = value {
                 notif = notif
                     .hint(Hint::CustomInt("value".to_string(), value as i32))
                     .finalize();
             }
-            let id = if let Ok(id) = self.logger.read() {
-                notif.id(id).show()?.id()
-            } else {
-                notif.show()?.id()
-            };
-            self.logger.write(&id)?;
+            self.show(notif)?;
+        }
+        Ok(())
+    }
+
+    // blocks until the notification is closed, true if its action was invoked,
+    // a previous question is closed first as replacing it would never return
+    pub fn ask(&self, summary: &str, body: &str, action: &str) -> anyhow::Result<bool> {
+        let mut invoked = false;
+        if let Some(mut notif) = Self::build(summary, body, None) {
+            self.close()?;
+            notif.action("cancel", action);
+            self.show(notif)?
+                .wait_for_action(|invocation| invoked = invocation == "cancel");
+        }
+        Ok(invoked)
+    }
+
+    pub fn close(&self) -> anyhow::Result<()> {
+        if let Ok(id) = self.logger.read() {
+            Connection::session()?.call_method(
+                Some(NOTIFICATIONS),
+                NOTIFICATIONS_PATH,
+                Some(NOTIFICATIONS),
+                "CloseNotification",
+                &id,
+            )?;
         }
         Ok(())
     }
+
+    fn build(summary: &str, body: &str, urgency: Option<Urgency>) -> Option<Notification> {
+        let compositor = compositor::detect()?;
+        let color = format!("#{}ee", compositor.get_color());
+        Some(
+            Notification::new()
+                .summary(summary)
+                .body(body)
+                .hint(Hint::Urgency(urgency.unwrap_or(Urgency::Normal)))
+                .hint(Hint::Custom("frcolor".to_string(), color))
+                .finalize(),
+        )
+    }
+
+    fn show(&self, mut notif: Notification) -> anyhow::Result<NotificationHandle> {
+        let handle = if let Ok(id) = self.logger.read() {
+            notif.id(id).show()?
+        } else {
+            notif.show()?
+        };
+        self.logger.write(&handle.id())?;
+        Ok(handle)
+    }
 }
@@ -0,0 +1,153 @@
+use crate::battery::{BatteryState, LOW};
+use crate::logger::Logger;
+use crate::notifier::Notifier;
+use crate::system::System;
+use battery::State;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use zbus::blocking::{Connection, MessageIterator, Proxy, ProxyBuilder};
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{CacheProperties, MatchRule, MessageType};
+
+const UPOWER: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+const UPOWER_DEVICE: &str = "org.freedesktop.UPower.Device";
+
+pub struct Peripherals;
+
+impl Peripherals {
+    pub fn listen() -> anyhow::Result<()> {
+        let bus = System::bus()?;
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .path_namespace(UPOWER_PATH)?
+            .build();
+        let signals = MessageIterator::for_match_rule(rule, bus, None)?;
+        let mut current = None;
+        let mut notified = HashSet::new();
+        Self::update(Self::get_state(bus)?, &mut current, &mut notified)?;
+        for signal in signals {
+            signal?;
+            Self::update(Self::get_state(bus)?, &mut current, &mut notified)?;
+        }
+        Ok(())
+    }
+
+    fn update(
+        state: Vec<PeripheralState>,
+        current: &mut Option<Vec<PeripheralState>>,
+        notified: &mut HashSet<String>,
+    ) -> anyhow::Result<()> {
+        if Some(&state) != current.as_ref() {
+            for peripheral in &state {
+                if peripheral.low() {
+                    if notified.insert(peripheral.path.clone()) {
+                        peripheral.notify()?;
+                    }
+                } else {
+                    notified.remove(&peripheral.path);
+                }
+            }
+            Logger::new("peripherals").write(&state)?;
+            *current = Some(state);
+        }
+        Ok(())
+    }
+
+    fn get_state(bus: &Connection) -> anyhow::Result<Vec<PeripheralState>> {
+        let devices: Vec<OwnedObjectPath> =
+            Self::proxy(bus, UPOWER_PATH, UPOWER)?.call("EnumerateDevices", &())?;
+        let mut peripherals = Vec::new();
+        for device in devices {
+            let proxy = Self::proxy(bus, device.as_str(), UPOWER_DEVICE)?;
+            if proxy.get_property::<bool>("PowerSupply")?
+                || !proxy.get_property::<bool>("IsPresent")?
+            {
+                continue;
+            }
+            peripherals.push(PeripheralState::new(
+                device.as_str(),
+                &proxy.get_property::<String>("Model")?,
+                proxy.get_property("Type")?,
+                proxy.get_property::<f64>("Percentage")?.round() as u32,
+                proxy.get_property("State")?,
+            ));
+        }
+        Ok(peripherals)
+    }
+
+    fn proxy<'a>(bus: &Connection, path: &'a str, interface: &'a str) -> anyhow::Result<Proxy<'a>> {
+        Ok(ProxyBuilder::new_bare(bus)
+            .destination(UPOWER)?
+            .path(path)?
+            .interface(interface)?
+            .cache_properties(CacheProperties::No)
+            .build()?)
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct PeripheralState {
+    path: String,
+    name: String,
+    kind: String,
+    value: u32,
+    charging: bool,
+    icon: String,
+}
+
+impl PeripheralState {
+    pub fn new(path: &str, name: &str, kind: u32, value: u32, state: u32) -> Self {
+        // see UpDeviceKind and UpDeviceState
+        let kind = match kind {
+            5 => "mouse",
+            6 => "keyboard",
+            8 => "phone",
+            9 => "media-player",
+            10 => "tablet",
+            12 => "gaming-input",
+            13 => "pen",
+            14 => "touchpad",
+            17 => "headset",
+            18 => "speakers",
+            19 => "headphones",
+            22 => "remote-control",
+            26 => "wearable",
+            _ => "other",
+        }
+        .to_string();
+        let state = match state {
+            1 | 5 => State::Charging,
+            3 => State::Empty,
+            4 => State::Full,
+            _ => State::Discharging,
+        };
+        let name = match name {
+            "" => kind.clone(),
+            _ => name.to_string(),
+        };
+        Self {
+            path: path.to_string(),
+            name,
+            kind,
+            value,
+            charging: state == State::Charging,
+            icon: BatteryState::icon(value, state),
+        }
+    }
+
+    pub fn low(&self) -> bool {
+        !self.charging && self.value < LOW
+    }
+
+    pub fn notify(&self) -> anyhow::Result<()> {
+        // one notification per device, e.g. 'mouse_dev_AA_BB_CC_DD_EE_FF'
+        let device = self.path.rsplit('/').next().unwrap_or(&self.kind);
+        Notifier::new(&format!("peripherals.{device}")).send(
+            &format!("{} battery low", self.name),
+            &format!("{}% remaining", self.value),
+            None,
+            Some(self.value),
+        )
+    }
+}
@@ -0,0 +1,397 @@
+use crate::brightness::Brightness;
+use crate::config::{Config, Idle, Profile};
+use crate::logger::Logger;
+use crate::manager::Manager;
+use crate::notifier::Notifier;
+use crate::system::System;
+use clap::{Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::Duration;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::OwnedValue;
+
+const SUPPLY: &str = "/sys/class/power_supply";
+
+// owned by the daemon, replaced whenever idle timeouts are applied
+static IDLE: Mutex<Option<Child>> = Mutex::new(None);
+
+pub struct Power;
+
+impl Power {
+    pub fn listen() -> anyhow::Result<()> {
+        let bus = System::bus()?;
+        let proxy = Self::proxy(bus)?;
+        let mut current = None;
+        let mut changes = proxy.receive_property_changed::<String>("ActiveProfile");
+        loop {
+            let state = ProfileState::new(&Self::get_profile(bus)?);
+            if Some(&state) != current.as_ref() {
+                if current.is_some() {
+                    state.notify()?;
+                }
+                state.log()?;
+                current = Some(state);
+            }
+            if changes.next().is_none() {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn online() -> anyhow::Result<bool> {
+        for entry in std::fs::read_dir(SUPPLY).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if std::fs::read_to_string(path.join("type")).is_ok_and(|t| t.trim() == "Mains") {
+                return Ok(std::fs::read_to_string(path.join("online"))?.trim() == "1");
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn get_profile(bus: &Connection) -> anyhow::Result<String> {
+        let profile = Self::proxy(bus)?.get_property("ActiveProfile")?;
+        Ok(profile)
+    }
+
+    pub fn get_profiles(bus: &Connection) -> anyhow::Result<Vec<String>> {
+        let profiles: Vec<HashMap<String, OwnedValue>> =
+            Self::proxy(bus)?.get_property("Profiles")?;
+        Ok(profiles
+            .into_iter()
+            .flat_map(|profile| profile.get("Profile").cloned())
+            .flat_map(String::try_from)
+            .collect())
+    }
+
+    pub fn set_profile(bus: &Connection, profile: &str) -> anyhow::Result<()> {
+        Self::proxy(bus)?.set_property("ActiveProfile", profile)?;
+        Ok(())
+    }
+
+    pub fn change_profile(operation: ProfileOp) -> anyhow::Result<()> {
+        let profile = Self::switch_profile(System::bus()?, operation)?;
+        let state = ProfileState::new(&profile);
+        state.notify()?;
+        state.log()
+    }
+
+    fn switch_profile(bus: &Connection, operation: ProfileOp) -> anyhow::Result<String> {
+        let profile = match operation {
+            ProfileOp::Next => {
+                let (profiles, current) = (Self::get_profiles(bus)?, Self::get_profile(bus)?);
+                let index = profiles.iter().position(|p| *p == current).unwrap_or(0);
+                profiles
+                    .get((index + 1) % profiles.len().max(1))
+                    .cloned()
+                    .unwrap_or(current)
+            }
+            _ => operation.to_string(),
+        };
+        Self::set_profile(bus, &profile)?;
+        Ok(profile)
+    }
+
+    // forced when the daemon starts, as nothing from the last session is applied
+    pub fn update(delay: u64, force: bool) -> anyhow::Result<()> {
+        if delay != 0 {
+            std::thread::sleep(Duration::from_millis(delay));
+        }
+        let prev = Logger::<PowerState>::new("power").read().ok();
+        let state = PowerState::new(Self::online()?);
+        let changed = prev.as_ref() != Some(&state);
+        if changed {
+            state.notify(prev)?;
+        }
+        if changed || force {
+            state.apply()?;
+        }
+        state.log()
+    }
+
+    pub fn handle(operation: PowerOp) -> anyhow::Result<()> {
+        match operation {
+            // the daemon owns swayidle
+            PowerOp::Update => Manager::request(),
+            PowerOp::Profile { operation } => Self::change_profile(operation),
+        }
+    }
+
+    fn idle(idle: Idle) -> anyhow::Result<()> {
+        let mut current = IDLE.lock().unwrap();
+        if let Some(mut child) = current.take() {
+            // it may have exited already, it still needs to be reaped
+            let _ = child.kill();
+            child.wait()?;
+        }
+        let child = Command::new("swayidle")
+            .args([
+                "-w",
+                "timeout",
+                &idle.lock.to_string(),
+                "emanager system lock",
+            ])
+            .args([
+                "timeout",
+                &idle.suspend.to_string(),
+                "emanager system suspend",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        *current = Some(child);
+        Ok(())
+    }
+
+    fn proxy(bus: &Connection) -> anyhow::Result<Proxy<'_>> {
+        Ok(Proxy::new(
+            bus,
+            "net.hadess.PowerProfiles",
+            "/net/hadess/PowerProfiles",
+            "net.hadess.PowerProfiles",
+        )?)
+    }
+}
+
+#[derive(Copy, Clone, Subcommand)]
+pub enum PowerOp {
+    /// Update power source and apply its profile
+    Update,
+    /// Change power profile
+    Profile {
+        #[arg(value_enum)]
+        operation: ProfileOp,
+    },
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum ProfileOp {
+    Performance,
+    Balanced,
+    PowerSaver,
+    Next,
+}
+
+impl Display for ProfileOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileOp::Performance => write!(f, "performance"),
+            ProfileOp::Balanced => write!(f, "balanced"),
+            ProfileOp::PowerSaver => write!(f, "power-saver"),
+            ProfileOp::Next => write!(f, "next"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct PowerState {
+    online: bool,
+    source: String,
+    icon: String,
+}
+
+impl PowerState {
+    pub fn new(online: bool) -> Self {
+        let (source, icon) = if online {
+            ("ac", "󰚥 ")
+        } else {
+            ("battery", "󰁹 ")
+        };
+        Self {
+            online,
+            source: source.to_string(),
+            icon: icon.to_string(),
+        }
+    }
+
+    pub fn notify(&self, prev: Option<Self>) -> anyhow::Result<()> {
+        let notifier = Notifier::new("power");
+        if self.online && prev.as_ref().is_some_and(|s| !s.online) {
+            notifier.send("Power", "Charger connected", None, None)?;
+        } else if !self.online && prev.as_ref().is_some_and(|s| s.online) {
+            notifier.send("Power", "Charger disconnected", None, None)?;
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self) -> anyhow::Result<()> {
+        let config = Config::read()?.power;
+        let profile: Profile = if self.online {
+            config.ac
+        } else {
+            config.battery
+        };
+        if let Some(percent) = profile.brightness {
+            Brightness::set(percent)?;
+        }
+        if let Some(idle) = profile.idle {
+            Power::idle(idle)?;
+        }
+        if let Some(profile) = profile.profile {
+            Power::set_profile(System::bus()?, &profile)?;
+        }
+        Ok(())
+    }
+
+    pub fn log(&self) -> anyhow::Result<()> {
+        Logger::new("power").write(self)
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct ProfileState {
+    profile: String,
+    icon: String,
+}
+
+impl ProfileState {
+    pub fn new(profile: &str) -> Self {
+        let icon = match profile {
+            "performance" => "󰓅 ",
+            "power-saver" => "󰾆 ",
+            _ => "󰾅 ",
+        }
+        .to_string();
+        Self {
+            profile: profile.to_string(),
+            icon,
+        }
+    }
+
+    pub fn notify(&self) -> anyhow::Result<()> {
+        Notifier::new("profile").send(
+            "Power profile",
+            &format!("Set to '{}'", self.profile),
+            None,
+            None,
+        )
+    }
+
+    pub fn log(&self) -> anyhow::Result<()> {
+        Logger::new("profile").write(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::Child;
+    use zbus::blocking::ConnectionBuilder;
+    use zbus::dbus_interface;
+    use zbus::zvariant::Value;
+
+    // a private dbus-daemon, killed when dropped
+    struct Bus(Child);
+
+    impl Bus {
+        fn start() -> (Self, String) {
+            let mut child = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("dbus-daemon is needed to run this test");
+            let mut address = String::new();
+            BufReader::new(child.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            (Self(child), address.trim().to_string())
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    struct PowerProfiles {
+        active: String,
+    }
+
+    #[dbus_interface(name = "net.hadess.PowerProfiles")]
+    impl PowerProfiles {
+        #[dbus_interface(property)]
+        fn active_profile(&self) -> String {
+            self.active.clone()
+        }
+
+        #[dbus_interface(property)]
+        fn set_active_profile(&mut self, profile: String) {
+            self.active = profile;
+        }
+
+        #[dbus_interface(property)]
+        fn profiles(&self) -> Vec<HashMap<String, OwnedValue>> {
+            ["power-saver", "balanced", "performance"]
+                .into_iter()
+                .map(|profile| {
+                    HashMap::from([
+                        ("Profile".to_string(), Value::from(profile).into()),
+                        ("Driver".to_string(), Value::from("mock").into()),
+                    ])
+                })
+                .collect()
+        }
+    }
+
+    fn serve(address: &str, active: &str) -> Connection {
+        let profiles = PowerProfiles {
+            active: active.to_string(),
+        };
+        ConnectionBuilder::address(address)
+            .unwrap()
+            .name("net.hadess.PowerProfiles")
+            .unwrap()
+            .serve_at("/net/hadess/PowerProfiles", profiles)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn client(address: &str) -> Connection {
+        ConnectionBuilder::address(address)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn get_profiles() {
+        let (_bus, address) = Bus::start();
+        let _service = serve(&address, "balanced");
+        let bus = client(&address);
+        assert_eq!(
+            Power::get_profiles(&bus).unwrap(),
+            ["power-saver", "balanced", "performance"]
+        );
+        assert_eq!(Power::get_profile(&bus).unwrap(), "balanced");
+    }
+
+    #[test]
+    fn next_profile_cycles() {
+        let (_bus, address) = Bus::start();
+        let _service = serve(&address, "balanced");
+        let bus = client(&address);
+        for expected in ["performance", "power-saver", "balanced"] {
+            let profile = Power::switch_profile(&bus, ProfileOp::Next).unwrap();
+            assert_eq!(profile, expected);
+            assert_eq!(Power::get_profile(&bus).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn set_named_profile() {
+        let (_bus, address) = Bus::start();
+        let _service = serve(&address, "balanced");
+        let bus = client(&address);
+        let profile = Power::switch_profile(&bus, ProfileOp::PowerSaver).unwrap();
+        assert_eq!(profile, "power-saver");
+        assert_eq!(Power::get_profile(&bus).unwrap(), "power-saver");
+    }
+}
@@ -0,0 +1,164 @@
+use crate::config::{Config, ScheduleConfig};
+use crate::logger::Logger;
+use crate::notifier::Notifier;
+use crate::system::{System, SystemAction, When};
+use crate::utils::{format_duration, timestamp};
+use anyhow::anyhow;
+use clap::Subcommand;
+use notify_rust::Urgency;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// owned by the daemon, each pending action has its own timer thread
+static PENDING: Mutex<Vec<Pending>> = Mutex::new(Vec::new());
+
+pub struct Schedule;
+
+impl Schedule {
+    // picks up actions scheduled before the daemon restarted, dropping missed ones
+    pub fn restore() -> anyhow::Result<()> {
+        let now = timestamp();
+        let restored = Logger::<Vec<Pending>>::new("schedule")
+            .read()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| p.at > now)
+            .collect::<Vec<Pending>>();
+        // the timers must run even when the config can't be read
+        let warnings = match Config::read() {
+            Ok(config) => config.schedule.warnings,
+            Err(e) => {
+                eprintln!("Failed to read the config, using default warnings ({e})");
+                ScheduleConfig::default().warnings
+            }
+        };
+        let mut pending = PENDING.lock().unwrap();
+        *pending = restored;
+        Self::log(&pending)?;
+        for id in pending.iter().map(|p| p.id) {
+            Self::spawn(id, warnings.clone());
+        }
+        Ok(())
+    }
+
+    pub fn add(action: SystemAction, when: &When) -> anyhow::Result<String> {
+        let at = when.timestamp()?;
+        let warnings = Config::read()?.schedule.warnings;
+        let mut pending = PENDING.lock().unwrap();
+        let id = pending.iter().map(|p| p.id + 1).max().unwrap_or(1);
+        pending.push(Pending { id, action, at });
+        Self::log(&pending)?;
+        drop(pending);
+        Self::spawn(id, warnings);
+        let body = format!("{action} in {}", Self::remaining(at));
+        Notifier::new("schedule").send("Scheduled", &body, None, None)?;
+        Ok(String::new())
+    }
+
+    pub fn handle(operation: ScheduleOp) -> anyhow::Result<String> {
+        match operation {
+            ScheduleOp::List => Ok(serde_json::to_string(&*PENDING.lock().unwrap())?),
+            ScheduleOp::Cancel { id } => Self::cancel(id),
+        }
+    }
+
+    fn cancel(id: Option<u32>) -> anyhow::Result<String> {
+        let mut pending = PENDING.lock().unwrap();
+        let (cancelled, kept) = pending
+            .drain(..)
+            .partition::<Vec<Pending>, _>(|p| id.is_none_or(|id| p.id == id));
+        *pending = kept;
+        if cancelled.is_empty() {
+            return Err(anyhow!("No scheduled action to cancel"));
+        }
+        Self::log(&pending)?;
+        drop(pending);
+        let notifier = Notifier::new("schedule");
+        for p in cancelled {
+            // lets a pending warning return
+            Self::warning(p.id).close()?;
+            notifier.send("Cancelled", &p.action.to_string(), None, None)?;
+        }
+        Ok(String::new())
+    }
+
+    fn spawn(id: u32, warnings: Vec<u64>) {
+        std::thread::spawn(move || {
+            if let Err(e) = Self::wait(id, warnings) {
+                eprintln!("{e}");
+            }
+        });
+    }
+
+    // ticks on the wall clock so that suspending doesn't delay the action
+    fn wait(id: u32, mut warnings: Vec<u64>) -> anyhow::Result<()> {
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let mut pending = PENDING.lock().unwrap();
+            let Some(index) = pending.iter().position(|p| p.id == id) else {
+                return Ok(());
+            };
+            let Pending { action, at, .. } = pending[index];
+            let remaining = at.saturating_sub(timestamp());
+            if remaining == 0 {
+                pending.remove(index);
+                Self::log(&pending)?;
+                drop(pending);
+                if let Err(e) = System::run(action) {
+                    let body = format!("{action} failed ({e})");
+                    let notifier = Notifier::new("schedule");
+                    notifier.send("Scheduled", &body, Some(Urgency::Critical), None)?;
+                    return Err(e);
+                }
+                return Self::warning(id).close();
+            }
+            if warnings.iter().any(|warning| remaining <= *warning) {
+                warnings.retain(|warning| remaining > *warning);
+                std::thread::spawn(move || Self::warn(id, action, at));
+            }
+        }
+    }
+
+    fn warn(id: u32, action: SystemAction, at: u64) {
+        let body = format!("{action} in {}", Self::remaining(at));
+        match Self::warning(id).ask("Scheduled", &body, "Cancel") {
+            Ok(true) => {
+                if let Err(e) = Self::cancel(Some(id)) {
+                    eprintln!("{e}");
+                }
+            }
+            Ok(false) => (),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+
+    // each action warns in its own notification, which the next warning replaces
+    fn warning(id: u32) -> Notifier {
+        Notifier::new(&format!("schedule.{id}"))
+    }
+
+    fn remaining(at: u64) -> String {
+        format_duration(Duration::from_secs(at.saturating_sub(timestamp())))
+    }
+
+    fn log(pending: &[Pending]) -> anyhow::Result<()> {
+        Logger::new("schedule").write(&pending.to_vec())
+    }
+}
+
+#[derive(Clone, Subcommand)]
+pub enum ScheduleOp {
+    /// List scheduled actions
+    List,
+    /// Cancel a scheduled action, or all of them
+    Cancel { id: Option<u32> },
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Pending {
+    id: u32,
+    action: SystemAction,
+    // unix timestamp at which the action runs
+    at: u64,
+}
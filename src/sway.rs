@@ -0,0 +1,572 @@
+use crate::compositor::{ClientState, Compositor, WindowState, WorkspaceState};
+use crate::config::{ColorMode, Config, LayoutConfig};
+use crate::logger::Logger;
+use anyhow::anyhow;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+// i3 IPC, see sway-ipc(7)
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+const EVENT_WORKSPACE: u32 = 0x80000000;
+const EVENT_WINDOW: u32 = 0x80000003;
+const EVENT_SHUTDOWN: u32 = 0x80000006;
+const EVENT_INPUT: u32 = 0x80000015;
+
+pub struct Sway {
+    socket: Option<PathBuf>,
+}
+
+impl Sway {
+    pub fn new() -> Self {
+        let socket = std::env::var("SWAYSOCK").or(std::env::var("I3SOCK")).ok();
+        Self::connect(socket.map(PathBuf::from))
+    }
+
+    pub fn connect(socket: Option<PathBuf>) -> Self {
+        Self { socket }
+    }
+
+    fn stream(&self) -> anyhow::Result<UnixStream> {
+        let socket = self.socket.as_ref().ok_or(anyhow!("SWAYSOCK is not set"))?;
+        Ok(UnixStream::connect(socket)?)
+    }
+
+    fn request(&self, kind: u32, payload: &str) -> anyhow::Result<Value> {
+        let mut stream = self.stream()?;
+        Self::send(&mut stream, kind, payload)?;
+        let (_, reply) = Self::receive(&mut stream)?;
+        Ok(reply)
+    }
+
+    fn command(&self, command: &str) -> anyhow::Result<()> {
+        let reply = self.request(RUN_COMMAND, command)?;
+        let results = reply.as_array().cloned().unwrap_or_default();
+        match results.iter().find(|result| result["success"] != true) {
+            Some(result) => Err(anyhow!("'{command}' failed: {}", result["error"])),
+            None => Ok(()),
+        }
+    }
+
+    fn subscribe(&self, events: &str) -> anyhow::Result<UnixStream> {
+        let mut stream = self.stream()?;
+        Self::send(&mut stream, SUBSCRIBE, events)?;
+        let (_, reply) = Self::receive(&mut stream)?;
+        if reply["success"] != true {
+            return Err(anyhow!("Subscribing to {events} failed"));
+        }
+        Ok(stream)
+    }
+
+    // sway compiles a keymap for each setting, so variants are cleared before
+    // loading every layout at once, then one of them is picked
+    fn switch_layout(&self, layouts: &[LayoutConfig], index: usize) -> anyhow::Result<()> {
+        let list = |field: fn(&LayoutConfig) -> &str| {
+            layouts.iter().map(field).collect::<Vec<&str>>().join(",")
+        };
+        let commands = [
+            "xkb_variant ''".to_string(),
+            format!("xkb_layout '{}'", list(|l| &l.layout)),
+            format!("xkb_variant '{}'", list(|l| &l.variant)),
+            format!("xkb_options '{}'", layouts[index].options),
+            format!("xkb_switch_layout {index}"),
+        ];
+        let commands = commands.map(|command| format!("input type:keyboard {command}"));
+        self.command(&commands.join("; "))
+    }
+
+    fn send(stream: &mut UnixStream, kind: u32, payload: &str) -> anyhow::Result<()> {
+        let mut message = MAGIC.to_vec();
+        message.extend((payload.len() as u32).to_ne_bytes());
+        message.extend(kind.to_ne_bytes());
+        message.extend(payload.as_bytes());
+        stream.write_all(&message)?;
+        Ok(())
+    }
+
+    fn receive(stream: &mut UnixStream) -> anyhow::Result<(u32, Value)> {
+        let mut header = [0; 14];
+        stream.read_exact(&mut header)?;
+        if &header[..6] != MAGIC {
+            return Err(anyhow!("Invalid IPC message"));
+        }
+        let length = u32::from_ne_bytes(header[6..10].try_into()?);
+        let kind = u32::from_ne_bytes(header[10..14].try_into()?);
+        let mut payload = vec![0; length as usize];
+        stream.read_exact(&mut payload)?;
+        Ok((kind, serde_json::from_slice(&payload)?))
+    }
+
+    fn clients(node: &Value, workspace: i32, icons: &HashMap<String, String>) -> Vec<ClientState> {
+        let workspace = match node["type"].as_str() {
+            Some("workspace") => node["num"].as_i64().unwrap_or(-1) as i32,
+            _ => workspace,
+        };
+        let mut clients = Vec::new();
+        if node["pid"].is_i64() {
+            let class = Self::class(node);
+            clients.push(ClientState {
+                address: node["id"].to_string(),
+                workspace,
+                title: node["name"].as_str().unwrap_or("").to_string(),
+                pid: node["pid"].as_i64().unwrap_or(0) as i32,
+                urgent: node["urgent"] == true,
+                fullscreen: node["fullscreen_mode"].as_i64().unwrap_or(0) != 0,
+                icon: WindowState::icon(&class, icons),
+                class,
+            });
+        }
+        for child in ["nodes", "floating_nodes"]
+            .iter()
+            .flat_map(|key| node[key].as_array())
+            .flatten()
+        {
+            clients.extend(Self::clients(child, workspace, icons));
+        }
+        clients
+    }
+
+    fn focused(node: &Value) -> Option<&Value> {
+        if node["focused"] == true {
+            return Some(node);
+        }
+        ["nodes", "floating_nodes"]
+            .iter()
+            .flat_map(|key| node[key].as_array())
+            .flatten()
+            .find_map(Self::focused)
+    }
+
+    // wayland windows have an app_id, xwayland ones an X11 class
+    fn class(node: &Value) -> String {
+        node["app_id"]
+            .as_str()
+            .or(node["window_properties"]["class"].as_str())
+            .unwrap_or("")
+            .to_string()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum SwayEvent {
+    Workspace { focus: bool },
+    Window { change: String, class: String },
+    Layout { name: String },
+    Shutdown,
+    Other,
+}
+
+impl SwayEvent {
+    fn from(kind: u32, event: &Value) -> Self {
+        let change = event["change"].as_str().unwrap_or("");
+        match kind {
+            EVENT_WORKSPACE => Self::Workspace {
+                focus: change == "focus",
+            },
+            EVENT_WINDOW => Self::Window {
+                change: change.to_string(),
+                class: Sway::class(&event["container"]),
+            },
+            EVENT_INPUT if change == "xkb_layout" || change == "xkb_keymap" => Self::Layout {
+                name: event["input"]["xkb_active_layout_name"]
+                    .as_str()
+                    .unwrap_or("")
+                    .to_string(),
+            },
+            EVENT_SHUTDOWN => Self::Shutdown,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl Compositor for Sway {
+    fn running(&self) -> bool {
+        self.stream().is_ok()
+    }
+
+    fn listen(&self) -> anyhow::Result<()> {
+        let mut stream = self.subscribe(r#"["workspace", "window", "input", "shutdown"]"#)?;
+        self.change_workspace()?;
+        self.change_window()?;
+        self.update_layout("")?;
+
+        loop {
+            let (kind, event) = Self::receive(&mut stream)?;
+            match SwayEvent::from(kind, &event) {
+                SwayEvent::Workspace { focus } => {
+                    self.change_workspace()?;
+                    if focus && Config::read()?.compositor.colors.mode == ColorMode::Workspace {
+                        self.change_color("")?;
+                    }
+                }
+                SwayEvent::Window { change, class } => {
+                    self.change_workspace()?;
+                    if ["focus", "title", "fullscreen_mode"].contains(&change.as_str()) {
+                        self.change_window()?;
+                    }
+                    if change == "focus" {
+                        self.change_color(&class)?;
+                    }
+                }
+                SwayEvent::Layout { name } => self.update_layout(&name)?,
+                SwayEvent::Shutdown => return Ok(()),
+                SwayEvent::Other => (),
+            }
+        }
+    }
+
+    fn get_workspaces(&self) -> anyhow::Result<Vec<WorkspaceState>> {
+        let icons = Config::read()?.compositor.icons;
+        let clients = Self::clients(&self.request(GET_TREE, "")?, -1, &icons);
+        let workspaces = self.request(GET_WORKSPACES, "")?;
+        Ok(workspaces
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|workspace| {
+                WorkspaceState::new(
+                    workspace["num"].as_i64().unwrap_or(-1) as i32,
+                    workspace["output"].as_str().unwrap_or(""),
+                    workspace["focused"] == true,
+                    workspace["visible"] == true,
+                    clients.clone(),
+                )
+            })
+            .collect())
+    }
+
+    fn get_workspace(&self) -> anyhow::Result<i32> {
+        let workspaces = self.request(GET_WORKSPACES, "")?;
+        workspaces
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|workspace| workspace["focused"] == true)
+            .and_then(|workspace| workspace["num"].as_i64())
+            .map(|num| num as i32)
+            .ok_or(anyhow!("No focused workspace"))
+    }
+
+    fn get_window(&self) -> anyhow::Result<WindowState> {
+        let icons = Config::read()?.compositor.icons;
+        let tree = self.request(GET_TREE, "")?;
+        // a focused workspace or output is not a window
+        let focused = Self::focused(&tree).filter(|node| node["pid"].is_i64());
+        Ok(WindowState::new(
+            &focused.map(Self::class).unwrap_or_default(),
+            focused.and_then(|node| node["name"].as_str()).unwrap_or(""),
+            focused.is_some_and(|node| node["fullscreen_mode"].as_i64().unwrap_or(0) != 0),
+            &icons,
+        ))
+    }
+
+    // sway only reports layout descriptions, so remember the last one we set
+    fn get_layout(&self) -> anyhow::Result<LayoutConfig> {
+        match Logger::<LayoutConfig>::new("layout.sway").read() {
+            Ok(layout) => Ok(layout),
+            Err(_) => Config::read()?
+                .compositor
+                .layouts
+                .first()
+                .cloned()
+                .ok_or(anyhow!("No layout configured")),
+        }
+    }
+
+    fn set_layout(&self, layout: &LayoutConfig) -> anyhow::Result<()> {
+        let layouts = Config::read()?.compositor.layouts;
+        let index = layouts
+            .iter()
+            .position(|l| l.layout == layout.layout && l.variant == layout.variant)
+            .ok_or(anyhow!("Layout '{layout}' is not configured"))?;
+        self.switch_layout(&layouts, index)?;
+        Logger::new("layout.sway").write(layout)
+    }
+
+    fn get_color(&self) -> String {
+        Logger::new("color")
+            .read()
+            .ok()
+            .or_else(|| {
+                let config = Config::read().ok()?;
+                config.compositor.colors.palette.first().cloned()
+            })
+            .unwrap_or("7aa2f7".to_string())
+    }
+
+    // no gradients here, the first color wins
+    fn set_color(&self, colors: &[String], _angle: u32) -> anyhow::Result<()> {
+        let color = colors.first().ok_or(anyhow!("No color given"))?;
+        self.command(&format!(
+            "client.focused #{color} #{color} #1a1b26 #{color} #{color}"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::os::unix::net::UnixListener;
+    use std::sync::{Arc, Mutex};
+
+    // a fake sway answering requests, every request it gets is recorded
+    struct Server {
+        socket: PathBuf,
+        requests: Arc<Mutex<Vec<(u32, String)>>>,
+    }
+
+    impl Server {
+        fn start(name: &str, events: Vec<(u32, Value)>) -> Self {
+            let socket = std::env::temp_dir()
+                .join(format!("emanager-sway-{}-{name}.sock", std::process::id()));
+            let _ = std::fs::remove_file(&socket);
+            let listener = UnixListener::bind(&socket).unwrap();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+            std::thread::spawn(move || {
+                for mut stream in listener.incoming().map_while(Result::ok) {
+                    while let Some((kind, payload)) = Self::read(&mut stream) {
+                        recorded.lock().unwrap().push((kind, payload));
+                        let reply = match kind {
+                            RUN_COMMAND => json!([{ "success": true }]),
+                            GET_WORKSPACES => workspaces(),
+                            SUBSCRIBE => json!({ "success": true }),
+                            GET_TREE => tree(),
+                            _ => json!({ "success": false }),
+                        };
+                        Sway::send(&mut stream, kind, &reply.to_string()).unwrap();
+                        if kind == SUBSCRIBE {
+                            for (kind, event) in &events {
+                                Sway::send(&mut stream, *kind, &event.to_string()).unwrap();
+                            }
+                        }
+                    }
+                }
+            });
+            Self { socket, requests }
+        }
+
+        // payloads aren't always json, e.g. GET_TREE sends nothing
+        fn read(stream: &mut UnixStream) -> Option<(u32, String)> {
+            let mut header = [0; 14];
+            stream.read_exact(&mut header).ok()?;
+            let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+            let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+            let mut payload = vec![0; length as usize];
+            stream.read_exact(&mut payload).ok()?;
+            Some((kind, String::from_utf8(payload).unwrap()))
+        }
+
+        fn sway(&self) -> Sway {
+            Sway::connect(Some(self.socket.clone()))
+        }
+
+        fn requests(&self) -> Vec<(u32, String)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.socket);
+        }
+    }
+
+    fn workspaces() -> Value {
+        json!([
+            { "num": 1, "output": "eDP-1", "focused": true, "visible": true },
+            { "num": 2, "output": "HDMI-A-1", "focused": false, "visible": true },
+        ])
+    }
+
+    fn tree() -> Value {
+        json!({
+            "type": "root",
+            "nodes": [{
+                "type": "output",
+                "name": "eDP-1",
+                "nodes": [{
+                    "type": "workspace",
+                    "num": 1,
+                    "nodes": [{
+                        "id": 10,
+                        "pid": 100,
+                        "app_id": "kitty",
+                        "name": "vim a, b.rs",
+                        "focused": true,
+                        "fullscreen_mode": 1,
+                    }],
+                    "floating_nodes": [{
+                        "id": 11,
+                        "pid": 101,
+                        "app_id": null,
+                        "window_properties": { "class": "steam" },
+                        "name": "Steam",
+                        "urgent": true,
+                    }],
+                }],
+            }, {
+                "type": "output",
+                "name": "HDMI-A-1",
+                "nodes": [{ "type": "workspace", "num": 2, "nodes": [] }],
+            }],
+        })
+    }
+
+    #[test]
+    fn framing() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        Sway::send(&mut client, GET_TREE, r#"{"a":1}"#).unwrap();
+        let mut message = [0; 21];
+        server.read_exact(&mut message).unwrap();
+        assert_eq!(&message[..6], b"i3-ipc");
+        assert_eq!(message[6..10], 7_u32.to_ne_bytes());
+        assert_eq!(message[10..14], GET_TREE.to_ne_bytes());
+        assert_eq!(&message[14..], br#"{"a":1}"#);
+
+        Sway::send(&mut server, EVENT_WINDOW, r#"{"change":"focus"}"#).unwrap();
+        let (kind, reply) = Sway::receive(&mut client).unwrap();
+        assert_eq!(kind, EVENT_WINDOW);
+        assert_eq!(reply, json!({ "change": "focus" }));
+
+        client.write_all(b"i3-bad\0\0\0\0\0\0\0\0").unwrap();
+        assert!(Sway::receive(&mut server).is_err());
+    }
+
+    #[test]
+    fn get_workspaces() {
+        let server = Server::start("workspaces", Vec::new());
+        let workspaces = server.sway().get_workspaces().unwrap();
+        assert_eq!(workspaces.len(), 2);
+
+        let first = &workspaces[0];
+        assert_eq!((first.id, first.monitor.as_str()), (1, "eDP-1"));
+        assert!(first.active && first.visible);
+        assert_eq!(first.windows, 2);
+        let classes = first.clients.iter().map(|c| c.class.as_str());
+        assert_eq!(classes.collect::<Vec<&str>>(), ["kitty", "steam"]);
+        assert_eq!(first.clients[0].address, "10");
+        assert_eq!(first.clients[0].title, "vim a, b.rs");
+        assert!(first.clients[0].fullscreen && !first.clients[0].urgent);
+        assert!(first.clients[1].urgent && !first.clients[1].fullscreen);
+
+        let second = &workspaces[1];
+        assert_eq!((second.id, second.monitor.as_str()), (2, "HDMI-A-1"));
+        assert!(!second.active && second.visible);
+        assert!(second.clients.is_empty());
+
+        assert_eq!(server.sway().get_workspace().unwrap(), 1);
+    }
+
+    #[test]
+    fn get_window() {
+        let server = Server::start("window", Vec::new());
+        let window = serde_json::to_value(server.sway().get_window().unwrap()).unwrap();
+        assert_eq!(window["class"], "kitty");
+        assert_eq!(window["title"], "vim a, b.rs");
+        assert_eq!(window["fullscreen"], true);
+        assert_eq!(server.requests(), [(GET_TREE, String::new())]);
+    }
+
+    #[test]
+    fn focused_workspace_is_no_window() {
+        let tree = json!({ "type": "workspace", "num": 1, "focused": true, "nodes": [] });
+        let focused = Sway::focused(&tree).filter(|node| node["pid"].is_i64());
+        assert!(focused.is_none());
+    }
+
+    #[test]
+    fn set_color_runs_a_command() {
+        let server = Server::start("color", Vec::new());
+        let colors = ["7aa2f7".to_string(), "9ece6a".to_string()];
+        server.sway().set_color(&colors, 45).unwrap();
+        assert_eq!(
+            server.requests(),
+            [(
+                RUN_COMMAND,
+                "client.focused #7aa2f7 #7aa2f7 #1a1b26 #7aa2f7 #7aa2f7".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn switch_layout_loads_every_layout() {
+        let server = Server::start("layout", Vec::new());
+        let mut intl = LayoutConfig::new("us");
+        intl.variant = "intl".to_string();
+        intl.options = "compose:ralt".to_string();
+        let layouts = [LayoutConfig::new("fr"), intl];
+        server.sway().switch_layout(&layouts, 1).unwrap();
+        let commands = [
+            "input type:keyboard xkb_variant ''",
+            "input type:keyboard xkb_layout 'fr,us'",
+            "input type:keyboard xkb_variant ',intl'",
+            "input type:keyboard xkb_options 'compose:ralt'",
+            "input type:keyboard xkb_switch_layout 1",
+        ];
+        assert_eq!(server.requests(), [(RUN_COMMAND, commands.join("; "))]);
+    }
+
+    #[test]
+    fn listen_events() {
+        let events = vec![
+            (EVENT_WORKSPACE, json!({ "change": "focus" })),
+            (EVENT_WORKSPACE, json!({ "change": "init" })),
+            (
+                EVENT_WINDOW,
+                json!({ "change": "focus", "container": { "app_id": "kitty" } }),
+            ),
+            (
+                EVENT_WINDOW,
+                json!({ "change": "title", "container": { "window_properties": { "class": "steam" } } }),
+            ),
+            (
+                EVENT_INPUT,
+                json!({ "change": "xkb_layout", "input": { "xkb_active_layout_name": "French" } }),
+            ),
+            (EVENT_INPUT, json!({ "change": "added", "input": {} })),
+            (EVENT_SHUTDOWN, json!({ "change": "exit" })),
+        ];
+        let server = Server::start("listen", events);
+        let mut stream = server.sway().subscribe(r#"["workspace"]"#).unwrap();
+        let mut received = Vec::new();
+        loop {
+            let (kind, event) = Sway::receive(&mut stream).unwrap();
+            received.push(SwayEvent::from(kind, &event));
+            if received.last() == Some(&SwayEvent::Shutdown) {
+                break;
+            }
+        }
+        assert_eq!(
+            received,
+            [
+                SwayEvent::Workspace { focus: true },
+                SwayEvent::Workspace { focus: false },
+                SwayEvent::Window {
+                    change: "focus".to_string(),
+                    class: "kitty".to_string(),
+                },
+                SwayEvent::Window {
+                    change: "title".to_string(),
+                    class: "steam".to_string(),
+                },
+                SwayEvent::Layout {
+                    name: "French".to_string(),
+                },
+                SwayEvent::Other,
+                SwayEvent::Shutdown,
+            ]
+        );
+        assert_eq!(
+            server.requests(),
+            [(SUBSCRIBE, r#"["workspace"]"#.to_string())]
+        );
+    }
+}
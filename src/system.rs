@@ -1,26 +1,64 @@
-use clap::{Subcommand, ValueEnum};
-use serde::Serialize;
+use crate::inhibit::InhibitOp;
+use crate::manager::Manager;
+use crate::schedule::ScheduleOp;
+use crate::utils::{parse_duration, timestamp};
+use anyhow::anyhow;
+use clap::{Args, Subcommand};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 use std::process::Command;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
-use zbus::{blocking::Connection, zvariant::DynamicType, Message};
+use zbus::zvariant::{DynamicType, OwnedObjectPath};
+use zbus::{blocking::Connection, Message};
+
+const LOGIN1: &str = "org.freedesktop.login1";
+const LOGIN1_MANAGER: &str = "org.freedesktop.login1.Manager";
+const LOGIN1_SESSION: &str = "org.freedesktop.login1.Session";
 
 #[derive(Clone)]
 pub struct System;
 
 impl System {
     pub fn poweroff() -> anyhow::Result<()> {
-        Self::call("poweroff", &true)?;
+        Self::check("PowerOff")?;
+        Self::call("PowerOff", &true)?;
         Ok(())
     }
 
     pub fn reboot() -> anyhow::Result<()> {
+        Self::check("Reboot")?;
         Self::call("Reboot", &true)?;
         Ok(())
     }
 
     pub fn suspend() -> anyhow::Result<()> {
-        Self::call("Suspend", &true).and_then(|_| Self::lock())
+        Self::sleep("Suspend")
+    }
+
+    pub fn hibernate() -> anyhow::Result<()> {
+        Self::sleep("Hibernate")
+    }
+
+    pub fn hybrid_sleep() -> anyhow::Result<()> {
+        Self::sleep("HybridSleep")
+    }
+
+    pub fn suspend_then_hibernate() -> anyhow::Result<()> {
+        Self::sleep("SuspendThenHibernate")
+    }
+
+    pub fn logout() -> anyhow::Result<()> {
+        let session: OwnedObjectPath =
+            Self::call("GetSessionByPID", &std::process::id())?.body()?;
+        Self::bus()?.call_method(
+            Some(LOGIN1),
+            session.as_str(),
+            Some(LOGIN1_SESSION),
+            "Terminate",
+            &(),
+        )?;
+        Ok(())
     }
 
     pub fn lock() -> anyhow::Result<()> {
@@ -29,40 +67,62 @@ impl System {
         Ok(())
     }
 
-    pub fn inhibit(operation: InhibitOp) -> anyhow::Result<()> {
-        match operation {
-            InhibitOp::On => {
-                let _handle =
-                    Self::call("Inhibit", &("idle", "emanager", "Idle inhibitor", "block"))?;
-                loop {
-                    std::thread::sleep(Duration::from_secs(2_u64.pow(8)));
-                }
-            }
-            InhibitOp::Off => {
-                Command::new("pkill")
-                    .args(["-f", "emanager system inhibit on"])
-                    .output()?;
-                Ok(())
-            }
+    // idle stays inhibited until the returned message, which holds the fd, is dropped
+    pub fn inhibitor(reason: &str) -> anyhow::Result<Arc<Message>> {
+        Self::call("Inhibit", &("idle", "emanager", reason, "block"))
+    }
+
+    pub fn run(action: SystemAction) -> anyhow::Result<()> {
+        match action {
+            SystemAction::Poweroff => Self::poweroff(),
+            SystemAction::Reboot => Self::reboot(),
+            SystemAction::Suspend => Self::suspend(),
+            SystemAction::Hibernate => Self::hibernate(),
+            SystemAction::HybridSleep => Self::hybrid_sleep(),
+            SystemAction::SuspendThenHibernate => Self::suspend_then_hibernate(),
+            SystemAction::Lock => Self::lock(),
+            SystemAction::Logout => Self::logout(),
         }
     }
 
     pub fn handle(operation: SystemOp) -> anyhow::Result<()> {
-        match operation {
-            SystemOp::Poweroff => Self::poweroff(),
-            SystemOp::Reboot => Self::reboot(),
-            SystemOp::Suspend => Self::suspend(),
-            SystemOp::Lock => Self::lock(),
-            SystemOp::Inhibit { operation } => Self::inhibit(operation),
+        match operation.action() {
+            Some((action, when)) if when.delay.is_none() && when.at.is_none() => Self::run(action),
+            // the daemon owns timers and the inhibitor
+            _ => Manager::request(),
         }
     }
 
-    fn call(method: &str, body: &(impl Serialize + DynamicType)) -> anyhow::Result<Arc<Message>> {
+    pub fn bus() -> anyhow::Result<&'static Connection> {
+        static BUS: OnceLock<Connection> = OnceLock::new();
+        if let Some(bus) = BUS.get() {
+            return Ok(bus);
+        }
         let bus = Connection::system()?;
+        Ok(BUS.get_or_init(|| bus))
+    }
+
+    fn sleep(method: &str) -> anyhow::Result<()> {
+        Self::check(method)?;
+        Self::call(method, &true).and_then(|_| Self::lock())
+    }
+
+    // login1 answers 'yes', 'no', 'challenge' or 'na' to its Can* methods
+    fn check(method: &str) -> anyhow::Result<()> {
+        let answer: String = Self::call(&format!("Can{method}"), &())?.body()?;
+        match answer.as_str() {
+            "yes" | "challenge" => Ok(()),
+            "na" => Err(anyhow!("{method} is not supported on this system")),
+            _ => Err(anyhow!("{method} is not allowed")),
+        }
+    }
+
+    fn call(method: &str, body: &(impl Serialize + DynamicType)) -> anyhow::Result<Arc<Message>> {
+        let bus = Self::bus()?;
         let message = bus.call_method(
-            Some("org.freedesktop.login1"),
+            Some(LOGIN1),
             "/org/freedesktop/login1",
-            Some("org.freedesktop.login1.Manager"),
+            Some(LOGIN1_MANAGER),
             method,
             body,
         )?;
@@ -70,25 +130,152 @@ impl System {
     }
 }
 
-#[derive(Copy, Clone, Subcommand)]
+#[derive(Clone, Subcommand)]
 pub enum SystemOp {
     /// Turn system off
-    Poweroff,
+    Poweroff {
+        #[command(flatten)]
+        when: When,
+    },
     /// Reboot system
-    Reboot,
+    Reboot {
+        #[command(flatten)]
+        when: When,
+    },
     /// Suspend system
-    Suspend,
+    Suspend {
+        #[command(flatten)]
+        when: When,
+    },
+    /// Hibernate system
+    Hibernate {
+        #[command(flatten)]
+        when: When,
+    },
+    /// Suspend and hibernate system
+    HybridSleep {
+        #[command(flatten)]
+        when: When,
+    },
+    /// Suspend system, then hibernate it after a while
+    SuspendThenHibernate {
+        #[command(flatten)]
+        when: When,
+    },
     /// Lock session
-    Lock,
+    Lock {
+        #[command(flatten)]
+        when: When,
+    },
+    /// End session
+    Logout {
+        #[command(flatten)]
+        when: When,
+    },
     /// Inhibit idle
     Inhibit {
-        #[arg(value_enum)]
+        #[command(subcommand)]
         operation: InhibitOp,
     },
+    /// Manage scheduled actions
+    Schedule {
+        #[command(subcommand)]
+        operation: ScheduleOp,
+    },
 }
 
-#[derive(Copy, Clone, ValueEnum)]
-pub enum InhibitOp {
-    On,
-    Off,
+impl SystemOp {
+    pub fn action(&self) -> Option<(SystemAction, When)> {
+        let (action, when) = match self {
+            Self::Poweroff { when } => (SystemAction::Poweroff, when),
+            Self::Reboot { when } => (SystemAction::Reboot, when),
+            Self::Suspend { when } => (SystemAction::Suspend, when),
+            Self::Hibernate { when } => (SystemAction::Hibernate, when),
+            Self::HybridSleep { when } => (SystemAction::HybridSleep, when),
+            Self::SuspendThenHibernate { when } => (SystemAction::SuspendThenHibernate, when),
+            Self::Lock { when } => (SystemAction::Lock, when),
+            Self::Logout { when } => (SystemAction::Logout, when),
+            Self::Inhibit { .. } | Self::Schedule { .. } => return None,
+        };
+        Some((action, when.clone()))
+    }
+}
+
+#[derive(Clone, Default, Args)]
+pub struct When {
+    /// Run after a delay, e.g. '30m'
+    #[arg(long = "in", value_parser = parse_duration, conflicts_with = "at")]
+    pub delay: Option<Duration>,
+    /// Run at a time, e.g. '23:00'
+    #[arg(long)]
+    pub at: Option<String>,
+}
+
+impl When {
+    pub fn timestamp(&self) -> anyhow::Result<u64> {
+        let now = timestamp();
+        match (&self.delay, &self.at) {
+            (Some(delay), _) => Ok(now + delay.as_secs()),
+            (None, Some(at)) => {
+                let output = Command::new("date").args(["-d", at, "+%s"]).output()?;
+                let time = String::from_utf8(output.stdout)?
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|_| anyhow!("Invalid time '{at}'"))?;
+                // '23:00' means tomorrow once it's past
+                Ok(if time <= now {
+                    time + 24 * 60 * 60
+                } else {
+                    time
+                })
+            }
+            (None, None) => Ok(now),
+        }
+    }
+}
+
+// actions that can be bound in the config, e.g. to the lid
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SystemAction {
+    Poweroff,
+    Reboot,
+    Suspend,
+    Hibernate,
+    HybridSleep,
+    SuspendThenHibernate,
+    Lock,
+    Logout,
+}
+
+impl Display for SystemAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Poweroff => "Poweroff",
+            Self::Reboot => "Reboot",
+            Self::Suspend => "Suspend",
+            Self::Hibernate => "Hibernate",
+            Self::HybridSleep => "Hybrid sleep",
+            Self::SuspendThenHibernate => "Suspend then hibernate",
+            Self::Lock => "Lock",
+            Self::Logout => "Logout",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl From<SystemAction> for SystemOp {
+    fn from(action: SystemAction) -> Self {
+        let when = When::default();
+        match action {
+            SystemAction::Poweroff => Self::Poweroff { when },
+            SystemAction::Reboot => Self::Reboot { when },
+            SystemAction::Suspend => Self::Suspend { when },
+            SystemAction::Hibernate => Self::Hibernate { when },
+            SystemAction::HybridSleep => Self::HybridSleep { when },
+            SystemAction::SuspendThenHibernate => Self::SuspendThenHibernate { when },
+            SystemAction::Lock => Self::Lock { when },
+            SystemAction::Logout => Self::Logout { when },
+        }
+    }
 }
@@ -3,3 +3,40 @@ pub fn utf8_to_u32(utf8: Vec<u8>) -> anyhow::Result<u32> {
     let value = string[0..string.len() - 1].trim().parse()?;
     Ok(value)
 }
+
+pub fn timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+pub fn parse_duration(string: &str) -> anyhow::Result<std::time::Duration> {
+    let string = string.trim();
+    let split = string
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(string.len());
+    let value = string[..split].parse::<u64>()?;
+    let unit = match &string[split..] {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        unit => return Err(anyhow::anyhow!("Unknown duration unit '{unit}'")),
+    };
+    let secs = value
+        .checked_mul(unit)
+        .ok_or(anyhow::anyhow!("Duration '{string}' is too long"))?;
+    Ok(std::time::Duration::from_secs(secs))
+}
+
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    match (hours, minutes, seconds) {
+        (0, 0, seconds) => format!("{seconds}s"),
+        (0, minutes, _) => format!("{minutes}m"),
+        (hours, 0, _) => format!("{hours}h"),
+        (hours, minutes, _) => format!("{hours}h{minutes:02}m"),
+    }
+}
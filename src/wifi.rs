@@ -1,41 +1,313 @@
+use crate::history::History;
 use crate::logger::Logger;
+use crate::network::Network;
 use crate::notifier::Notifier;
+use crate::utils::{parse_duration, timestamp};
+use anyhow::anyhow;
+use clap::Subcommand;
+use notify_rust::Urgency;
 use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
-use std::process::{Command, Output};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
 use std::time::Duration;
+use zbus::blocking::Connection;
+use zbus::zvariant::OwnedObjectPath;
 
 const PROGRAM: &str = "nmcli";
+const NM_WIRELESS: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+const NM_POINT: &str = "org.freedesktop.NetworkManager.AccessPoint";
 
 pub struct Wifi;
 
 impl Wifi {
-    pub fn listen() -> anyhow::Result<()> {
-        let mut current = None;
-        loop {
-            let state = Self::get_state()?;
-            if Some(&state) != current.as_ref() {
-                state.notify(current)?;
-                state.log()?;
-                current = Some(state);
+    pub fn list() -> anyhow::Result<()> {
+        let known = Self::known()?
+            .into_iter()
+            .map(|(_, ssid)| ssid)
+            .collect::<Vec<String>>();
+        let output = Self::run(&["-t", "-f", "in-use,ssid,signal,security", "dev", "wifi"])?;
+        let mut networks: Vec<WifiNetwork> = Vec::new();
+        for line in output.lines() {
+            let info = Self::split(line);
+            let ssid = info.get(1).cloned().unwrap_or_default();
+            if ssid.is_empty() {
+                continue;
+            }
+            let network = WifiNetwork {
+                known: known.contains(&ssid),
+                in_use: info.first().is_some_and(|i| i == "*"),
+                signal: info.get(2).and_then(|i| i.parse().ok()).unwrap_or(0),
+                security: info.get(3).cloned().unwrap_or_default(),
+                ssid,
+            };
+            match networks.iter_mut().find(|n| n.ssid == network.ssid) {
+                Some(other) => {
+                    other.in_use |= network.in_use;
+                    other.signal = other.signal.max(network.signal);
+                }
+                None => networks.push(network),
             }
-            std::thread::sleep(Duration::from_secs(2));
         }
+        networks.sort_by(|a, b| b.in_use.cmp(&a.in_use).then(b.signal.cmp(&a.signal)));
+        println!("{}", serde_json::to_string(&networks)?);
+        Ok(())
+    }
+
+    pub fn connect(ssid: &str, password: Option<&str>) -> anyhow::Result<()> {
+        let known = Self::known()?;
+        let profile = known.iter().find(|(_, known)| known == ssid);
+        let result = if let (Some((uuid, _)), None) = (profile, password) {
+            Self::run(&["connection", "up", "uuid", uuid])
+        } else {
+            let args = ["dev", "wifi", "connect", ssid];
+            match password {
+                Some(password) => Self::ask(&args, password),
+                None => Self::run(&args),
+            }
+        };
+        Self::report(result, &format!("Connected to '{ssid}'"))
     }
 
-    fn get_state() -> anyhow::Result<WifiState> {
-        let output = Self::exec(&["-t", "-f", "active,ssid,signal", "dev", "wifi"])?;
-        Ok(String::from_utf8(output.stdout)?
+    pub fn disconnect() -> anyhow::Result<()> {
+        let output = Self::run(&["-t", "-f", "device,type,state", "dev"])?;
+        let device = output
             .lines()
-            .map(|line| {
-                let info = line.split(':').collect::<Vec<&str>>();
-                let active = info.get(0).is_some_and(|i| i == &"yes");
-                let ssid = info.get(1).unwrap_or(&"");
-                let signal = info.get(2).unwrap_or(&"0").parse::<u32>().unwrap_or(0);
-                WifiState::new(active, ssid, signal)
+            .map(Self::split)
+            .find(|info| {
+                info.get(1).is_some_and(|i| i == "wifi")
+                    && info.get(2).is_some_and(|i| i == "connected")
             })
-            .find(|state| state.active)
-            .unwrap_or(WifiState::new(false, "", 0)))
+            .map(|info| info[0].clone())
+            .ok_or(anyhow!("Wi-Fi is not connected"))?;
+        let result = Self::run(&["dev", "disconnect", &device]);
+        Self::report(result, "Disconnected")
+    }
+
+    pub fn forget(ssid: &str) -> anyhow::Result<()> {
+        let uuids = Self::known()?
+            .into_iter()
+            .filter(|(_, known)| known == ssid)
+            .map(|(uuid, _)| uuid)
+            .collect::<Vec<String>>();
+        if uuids.is_empty() {
+            return Err(anyhow!("Network '{ssid}' is not known"));
+        }
+        let mut args = vec!["connection", "delete", "uuid"];
+        args.extend(uuids.iter().map(String::as_str));
+        let result = Self::run(&args);
+        Self::report(result, &format!("Forgot '{ssid}'"))
+    }
+
+    pub fn radio(on: bool) -> anyhow::Result<()> {
+        let (arg, message) = if on {
+            ("on", "Turned on")
+        } else {
+            ("off", "Turned off")
+        };
+        let result = Self::run(&["radio", "wifi", arg]);
+        Self::report(result, message)
+    }
+
+    pub fn stats(since: Duration) -> anyhow::Result<()> {
+        let now = timestamp();
+        let start = now.saturating_sub(since.as_secs());
+        let samples = History::<WifiSample>::new("wifi")
+            .read()?
+            .into_iter()
+            .filter(|sample| sample.0 >= start)
+            .collect::<Vec<WifiSample>>();
+        let mut stats: Vec<WifiStats> = Vec::new();
+        for (index, sample) in samples.iter().enumerate() {
+            if sample.1.is_empty() {
+                continue;
+            }
+            let stat = match stats.iter_mut().find(|stat| stat.ssid == sample.1) {
+                Some(stat) => stat,
+                None => {
+                    stats.push(WifiStats::new(&sample.1));
+                    stats.last_mut().unwrap()
+                }
+            };
+            // a sample holds until the next one, the last one until now
+            let next = samples.get(index + 1);
+            let until = next.map_or(now, |next| next.0);
+            stat.add(sample, until.saturating_sub(sample.0));
+            if next.is_some_and(|next| next.1.is_empty()) {
+                stat.disconnects += 1;
+            }
+        }
+        stats.sort_by_key(|stat| std::cmp::Reverse(stat.last_seen));
+        println!("{}", serde_json::to_string(&stats)?);
+        Ok(())
+    }
+
+    pub fn record(state: Option<&WifiState>, prev: Option<&WifiState>) -> anyhow::Result<()> {
+        let history = History::new("wifi");
+        match state {
+            Some(state) => history.push(&WifiSample(
+                timestamp(),
+                state.ssid.clone(),
+                state.bssid.clone(),
+                state.signal,
+            )),
+            None if prev.is_some() => {
+                history.push(&WifiSample(timestamp(), String::new(), String::new(), 0))
+            }
+            None => Ok(()),
+        }
+    }
+
+    // the eww config reads this one, it still has to follow the network state
+    pub fn log(state: Option<&WifiState>) -> anyhow::Result<()> {
+        let link = match state {
+            Some(state) => WifiLink {
+                active: true,
+                ssid: state.ssid.clone(),
+                icon: state.icon.clone(),
+            },
+            None => WifiLink {
+                active: false,
+                ssid: String::new(),
+                icon: "󰤭 ".to_string(),
+            },
+        };
+        Logger::new("wifi").write(&link)
+    }
+
+    pub fn handle(operation: WifiOp) -> anyhow::Result<()> {
+        match operation {
+            WifiOp::List => Self::list(),
+            WifiOp::Connect { ssid, password } => Self::connect(&ssid, password.as_deref()),
+            WifiOp::Disconnect => Self::disconnect(),
+            WifiOp::Forget { ssid } => Self::forget(&ssid),
+            WifiOp::On => Self::radio(true),
+            WifiOp::Off => Self::radio(false),
+            WifiOp::Stats { since } => Self::stats(since),
+        }
+    }
+
+    pub fn get_state_nm(bus: &Connection, device: &str) -> anyhow::Result<Option<WifiState>> {
+        let Some(point) = Self::get_point(bus, device)? else {
+            return Ok(None);
+        };
+        let wireless = Network::proxy(bus, device, NM_WIRELESS)?;
+        let bitrate = wireless.get_property::<u32>("Bitrate")? / 1000;
+        let point = Network::proxy(bus, point.as_str(), NM_POINT)?;
+        let ssid: Vec<u8> = point.get_property("Ssid")?;
+        let bssid: String = point.get_property("HwAddress")?;
+        let signal: u8 = point.get_property("Strength")?;
+        let frequency: u32 = point.get_property("Frequency")?;
+        let ssid = String::from_utf8_lossy(&ssid);
+        Ok(Some(WifiState::new(
+            &ssid,
+            &bssid,
+            signal as u32,
+            frequency,
+            bitrate,
+        )))
+    }
+
+    pub fn get_point(bus: &Connection, device: &str) -> anyhow::Result<Option<OwnedObjectPath>> {
+        let wireless = Network::proxy(bus, device, NM_WIRELESS)?;
+        let point: OwnedObjectPath = wireless.get_property("ActiveAccessPoint")?;
+        Ok((point.as_str() != "/").then_some(point))
+    }
+
+    pub fn get_state_nmcli() -> anyhow::Result<Option<WifiState>> {
+        let fields = "active,ssid,bssid,signal,freq,rate";
+        let output = Self::run(&["-t", "-f", fields, "dev", "wifi"])?;
+        // numbers come with their unit, e.g. '5180 MHz' or '270 Mbit/s'
+        let number = |info: &[String], index: usize| {
+            info.get(index)
+                .and_then(|i| i.split(' ').next()?.parse().ok())
+                .unwrap_or(0)
+        };
+        Ok(output
+            .lines()
+            .map(Self::split)
+            .find(|info| info.first().is_some_and(|i| i == "yes"))
+            .map(|info| {
+                let ssid = info.get(1).cloned().unwrap_or_default();
+                let bssid = info.get(2).cloned().unwrap_or_default();
+                WifiState::new(
+                    &ssid,
+                    &bssid,
+                    number(&info, 3),
+                    number(&info, 4),
+                    number(&info, 5),
+                )
+            }))
+    }
+
+    // uuid and ssid of saved Wi-Fi profiles, whose names may differ from the ssid
+    fn known() -> anyhow::Result<Vec<(String, String)>> {
+        let output = Self::run(&["-t", "-f", "uuid,type", "connection", "show"])?;
+        let mut known = Vec::new();
+        for info in output.lines().map(Self::split) {
+            if info.get(1).is_some_and(|i| i == "802-11-wireless") {
+                let ssid =
+                    Self::run(&["-g", "802-11-wireless.ssid", "connection", "show", &info[0]])?;
+                known.push((info[0].clone(), ssid.trim_end_matches('\n').to_string()));
+            }
+        }
+        Ok(known)
+    }
+
+    fn report(result: anyhow::Result<String>, success: &str) -> anyhow::Result<()> {
+        let notifier = Notifier::new("wifi");
+        match result {
+            Ok(_) => notifier.send("Wi-Fi", success, None, None),
+            Err(e) => {
+                notifier.send("Wi-Fi", &e.to_string(), Some(Urgency::Critical), None)?;
+                Err(e)
+            }
+        }
+    }
+
+    // split nmcli terse output, which escapes ':' as '\:'
+    pub fn split(line: &str) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => fields.last_mut().unwrap().extend(chars.next()),
+                ':' => fields.push(String::new()),
+                _ => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    pub fn run(args: &[impl AsRef<OsStr>]) -> anyhow::Result<String> {
+        Self::read(Self::exec(args)?)
+    }
+
+    // secrets are answered on stdin, the command line is readable by anyone
+    fn ask(args: &[impl AsRef<OsStr>], secret: &str) -> anyhow::Result<String> {
+        let mut child = Command::new(PROGRAM)
+            .arg("--ask")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            writeln!(stdin, "{secret}")?;
+        }
+        Self::read(child.wait_with_output()?)
+    }
+
+    fn read(output: Output) -> anyhow::Result<String> {
+        if output.status.success() {
+            Ok(String::from_utf8(output.stdout)?)
+        } else {
+            let error = String::from_utf8(output.stderr)?;
+            Err(anyhow!(error
+                .trim()
+                .trim_start_matches("Error: ")
+                .to_string()))
+        }
     }
 
     fn exec(args: &[impl AsRef<OsStr>]) -> anyhow::Result<Output> {
@@ -44,18 +316,62 @@ impl Wifi {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq)]
-struct WifiState {
-    active: bool,
+#[derive(Clone, Subcommand)]
+pub enum WifiOp {
+    /// List available networks
+    List,
+    /// Connect to a network
+    Connect {
+        ssid: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Disconnect from current network
+    Disconnect,
+    /// Forget a known network
+    Forget { ssid: String },
+    /// Turn radio on
+    On,
+    /// Turn radio off
+    Off,
+    /// Print signal history statistics per network
+    Stats {
+        #[arg(long, default_value = "7d", value_parser = parse_duration)]
+        since: Duration,
+    },
+}
+
+#[derive(Serialize)]
+struct WifiNetwork {
     ssid: String,
-    icon: String,
+    signal: u32,
+    security: String,
+    known: bool,
+    in_use: bool,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct WifiState {
+    pub ssid: String,
+    pub bssid: String,
+    pub signal: u32,
+    pub frequency: u32,
+    pub band: String,
+    pub bitrate: u32,
+    pub icon: String,
 }
 
 impl WifiState {
-    pub fn new(active: bool, ssid: &str, signal: u32) -> Self {
-        let icon = if !active {
-            "󰤭 "
-        } else if signal >= 75 {
+    pub fn new(ssid: &str, bssid: &str, signal: u32, frequency: u32, bitrate: u32) -> Self {
+        let band = if frequency >= 5925 {
+            "6GHz"
+        } else if frequency >= 4900 {
+            "5GHz"
+        } else {
+            "2.4GHz"
+        }
+        .to_string();
+        let icon = if signal >= 75 {
             "󰤨 "
         } else if signal >= 50 {
             "󰤥 "
@@ -65,31 +381,67 @@ impl WifiState {
             "󰤟 "
         }
         .to_string();
-        let ssid = ssid.to_string();
-        Self { active, ssid, icon }
+        Self {
+            ssid: ssid.to_string(),
+            bssid: bssid.to_string(),
+            signal,
+            frequency,
+            band,
+            bitrate,
+            icon,
+        }
     }
+}
 
-    pub fn notify(&self, prev: Option<Self>) -> anyhow::Result<()> {
-        let notifier = Notifier::new("wifi");
-        if self.active && !prev.as_ref().is_some_and(|s| s.active) {
-            notifier.send(
-                "Wi-Fi",
-                &format!("Connected to '{}'", self.ssid),
-                None,
-                None,
-            )?;
-        } else if !self.active && prev.as_ref().is_some_and(|s| s.active) {
-            notifier.send(
-                "Wi-Fi",
-                &format!("Disconnected from '{}'", prev.unwrap().ssid),
-                None,
-                None,
-            )?;
+#[derive(Serialize, Deserialize)]
+struct WifiLink {
+    active: bool,
+    ssid: String,
+    icon: String,
+}
+
+// time, ssid, bssid, signal, an empty ssid marks a disconnection
+#[derive(Serialize, Deserialize)]
+struct WifiSample(u64, String, String, u32);
+
+#[derive(Serialize)]
+struct WifiStats {
+    ssid: String,
+    bssids: Vec<String>,
+    average_signal: u32,
+    min_signal: u32,
+    disconnects: u32,
+    last_seen: u64,
+    #[serde(skip)]
+    total: u64,
+    #[serde(skip)]
+    duration: u64,
+}
+
+impl WifiStats {
+    pub fn new(ssid: &str) -> Self {
+        Self {
+            ssid: ssid.to_string(),
+            bssids: Vec::new(),
+            average_signal: 0,
+            min_signal: 100,
+            disconnects: 0,
+            last_seen: 0,
+            total: 0,
+            duration: 0,
         }
-        Ok(())
     }
 
-    pub fn log(&self) -> anyhow::Result<()> {
-        Logger::new("wifi").write(self)
+    // signal is averaged over time, samples only being written on changes
+    pub fn add(&mut self, sample: &WifiSample, duration: u64) {
+        if !self.bssids.contains(&sample.2) {
+            self.bssids.push(sample.2.clone());
+        }
+        let duration = duration.max(1);
+        self.total += sample.3 as u64 * duration;
+        self.duration += duration;
+        self.average_signal = (self.total / self.duration) as u32;
+        self.min_signal = self.min_signal.min(sample.3);
+        self.last_seen = sample.0;
     }
 }